@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use futures_lite::{future, StreamExt};
+use souvlaki::{
+    AsyncMediaControls, MediaControlEvent, MediaMetadata, MediaPlayback, PlatformConfig,
+};
+
+fn main() {
+    // Any runtime works, e.g. tokio or async-std.
+    future::block_on(async {
+        let config = PlatformConfig {
+            dbus_name: "my_player",
+            display_name: "My Player",
+            ..Default::default()
+        };
+        let mut controls = AsyncMediaControls::new(config).unwrap();
+        // The service would usually be spawned on the runtime, e.g. with `tokio::spawn`.
+        let service = controls.attach().await.unwrap();
+        future::or(service, run(controls)).await;
+    });
+}
+
+async fn run(mut controls: AsyncMediaControls) {
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Souvlaki Space Station"),
+            artist: Some("Slowdive"),
+            album: Some("Souvlaki"),
+            duration: Some(Duration::from_secs(360)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let mut playing = false;
+    let mut events = controls.events();
+
+    while let Some(event) = events.next().await {
+        println!("Event received: {:?}", event);
+
+        playing = match event {
+            MediaControlEvent::Play => true,
+            MediaControlEvent::Pause | MediaControlEvent::Stop => false,
+            MediaControlEvent::Toggle => !playing,
+            MediaControlEvent::Quit => break,
+            _ => continue,
+        };
+
+        let playback = if playing {
+            MediaPlayback::Playing { progress: None }
+        } else {
+            MediaPlayback::Paused { progress: None }
+        };
+        controls.set_playback(playback).await.unwrap();
+    }
+
+    controls.detach().await.unwrap();
+}
@@ -35,6 +35,7 @@ fn main() {
         dbus_name: "my_player",
         display_name: "My Player",
         hwnd,
+        ..Default::default()
     };
 
     let mut controls = MediaControls::new(config).unwrap();
@@ -56,6 +57,7 @@ fn main() {
             artist: Some("Slowdive"),
             duration: Some(Duration::from_secs_f64(4.0 * 60.0 + 50.0)),
             cover_url: Some("https://c.pxhere.com/photos/34/c1/souvlaki_authentic_greek_greek_food_mezes-497780.jpg!d"),
+            ..Default::default()
         })
         .unwrap();
 
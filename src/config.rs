@@ -1,12 +1,58 @@
 use std::ffi::c_void;
 
+use crate::Capabilities;
+
 /// OS-specific configuration needed to create media controls.
-#[derive(Debug)]
+#[derive(Clone, Debug, Default)]
 pub struct PlatformConfig<'a> {
     /// The name to be displayed to the user. (*Required on Linux*)
     pub display_name: &'a str,
     /// Should follow [the D-Bus spec](https://dbus.freedesktop.org/doc/dbus-specification.html#message-protocol-names-bus). (*Required on Linux*)
     pub dbus_name: &'a str,
+    /// Append `.instance<pid>` to the D-Bus name, so that several instances of the
+    /// application can publish media controls at the same time, as suggested by the MPRIS
+    /// specification. (*Only used on Linux*)
+    pub unique_dbus_name: bool,
+    /// The D-Bus connection on which the media controls are published. Defaults to a new
+    /// connection to the session bus. (*Only used on Linux*)
+    pub dbus_connection: DbusConnection,
+    /// The name of the application's `.desktop` file, without the extension, so that
+    /// desktop shells can show its icon. (*Only used on Linux*)
+    pub desktop_entry: &'a str,
+    /// The URI schemes that the media player can open, e.g. `file` or `https`. If any are
+    /// given, requests to open or add a URI with another scheme are rejected. Otherwise every
+    /// URI is sent to the event handler. (*Only used on Linux*)
+    pub supported_uri_schemes: &'a [&'a str],
+    /// The MIME types that the media player can open, e.g. `audio/mpeg`. If any are given,
+    /// requests to open or add a local file whose extension stands for another MIME type are
+    /// rejected. (*Only used on Linux*)
+    pub supported_mime_types: &'a [&'a str],
     /// An HWND. (*Required on Windows*)
     pub hwnd: Option<*mut c_void>,
+    /// The actions supported by the media player. Can be changed later with
+    /// [`MediaControls::set_capabilities`](crate::MediaControls::set_capabilities).
+    pub capabilities: Capabilities,
+}
+
+/// A D-Bus connection on which the MPRIS media controls are published.
+#[derive(Clone, Debug, Default)]
+pub enum DbusConnection {
+    /// A new connection to the session bus, as expected by MPRIS clients.
+    #[default]
+    Session,
+    /// A new connection to the system bus.
+    System,
+    /// A new connection to the bus at the given address, e.g. `unix:path=/tmp/bus`.
+    Address(String),
+    /// An existing connection of the application, which the media controls share instead
+    /// of opening their own. Only supported by `ZbusBackend` and `AsyncMediaControls`.
+    ///
+    /// The connection isn't replaced if it is lost. It must keep its internal executor,
+    /// unless the service is run by `ZbusBackend::attach_future`.
+    #[cfg(all(
+        feature = "zbus",
+        unix,
+        not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+    ))]
+    Zbus(zbus::Connection),
 }
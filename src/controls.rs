@@ -0,0 +1,381 @@
+use std::any::Any;
+use std::fmt::Debug;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::platform::DefaultBackend;
+use crate::{
+    Capabilities, Error, EventError, EventResult, LoopStatus, MediaControlEvent, MediaMetadata,
+    MediaPlayback, MediaPlaylist, MediaPosition, MetadataPatch, OwnedMediaMetadata, PlatformConfig,
+    PlaylistOrdering,
+};
+
+/// An event handler, as given to [`MediaControlsBackend::attach`].
+pub type BoxedEventHandler = Box<dyn FnMut(MediaControlEvent) -> Result<(), EventError> + Send>;
+
+/// Boxes an event handler, and turns its panics into [`EventError::Failed`] so that they
+/// can't take down the service which called it.
+pub(crate) fn box_event_handler<F, R>(mut event_handler: F) -> BoxedEventHandler
+where
+    F: FnMut(MediaControlEvent) -> R + Send + 'static,
+    R: EventResult,
+{
+    Box::new(move |event| {
+        panic::catch_unwind(AssertUnwindSafe(|| event_handler(event).into_result())).unwrap_or_else(
+            |payload| {
+                Err(EventError::Failed(format!(
+                    "event handler panicked: {}",
+                    panic_message(&*payload)
+                )))
+            },
+        )
+    })
+}
+
+/// The message of a panic, as given to `panic!`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic payload".to_string()
+    }
+}
+
+/// An implementation of OS media controls, which [`MediaControls`] forwards its calls to.
+///
+/// Every platform provides at least one backend, and [`NoopBackend`](crate::NoopBackend) is
+/// available everywhere. The methods which only make sense on MPRIS do nothing by default.
+pub trait MediaControlsBackend: Send {
+    /// Attach the media control events to a handler.
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error>;
+
+    /// Detach the event handler.
+    fn detach(&mut self) -> Result<(), Error>;
+
+    /// Set the current playback status.
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error>;
+
+    /// Set the metadata of the currently playing media item.
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error>;
+
+    /// Change some fields of the metadata, and keep the others. Nothing is published if the
+    /// metadata doesn't change.
+    fn update_metadata(&mut self, patch: MetadataPatch) -> Result<(), Error> {
+        match patch.applied_to(&self.metadata()) {
+            Some(metadata) => self.set_metadata(metadata),
+            None => Ok(()),
+        }
+    }
+
+    /// Set whether shuffle is enabled.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error>;
+
+    /// Set the loop/repeat status.
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error>;
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error>;
+
+    /// Set the actions supported by the media player.
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error>;
+
+    /// Set the volume level (0.0-1.0) (Only used on MPRIS)
+    fn set_volume(&mut self, _volume: f64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the range of playback rates that clients may request. (Only used on MPRIS)
+    fn set_rate_range(&mut self, _minimum: f64, _maximum: f64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set whether the media player is in fullscreen. (Only used on MPRIS)
+    fn set_fullscreen(&mut self, _fullscreen: bool) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Notify clients that the position jumped to `position`. (Only used on MPRIS)
+    fn notify_seeked(&mut self, _position: MediaPosition) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Replace the whole tracklist, optionally specifying the id of the current track.
+    /// (Only used on MPRIS)
+    fn set_tracklist(
+        &mut self,
+        _tracks: &[(&str, MediaMetadata)],
+        _current: Option<&str>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Insert a track into the tracklist, right after the track `after`. (Only used on MPRIS)
+    fn insert_track(
+        &mut self,
+        _id: &str,
+        _metadata: MediaMetadata,
+        _after: Option<&str>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Remove a track from the tracklist. (Only used on MPRIS)
+    fn remove_track(&mut self, _id: &str) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the metadata of a track in the tracklist. (Only used on MPRIS)
+    fn set_track_metadata(&mut self, _id: &str, _metadata: MediaMetadata) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the playlists which can be activated by the user. (Only used on MPRIS)
+    fn set_playlists(&mut self, _playlists: &[MediaPlaylist]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the id of the currently active playlist, or `None` if there isn't one.
+    /// (Only used on MPRIS)
+    fn set_active_playlist(&mut self, _id: Option<&str>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// Set the orderings in which the playlists can be listed. (Only used on MPRIS)
+    fn set_playlist_orderings(&mut self, _orderings: &[PlaylistOrdering]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    /// The metadata currently published.
+    fn metadata(&self) -> OwnedMediaMetadata;
+
+    /// The playback status currently published.
+    fn playback(&self) -> MediaPlayback;
+
+    /// The volume level currently published. (Only used on MPRIS)
+    fn volume(&self) -> f64 {
+        1.0
+    }
+
+    /// The actions currently published as supported.
+    fn capabilities(&self) -> Capabilities;
+}
+
+/// A handle to OS media controls.
+pub struct MediaControls {
+    backend: Box<dyn MediaControlsBackend>,
+}
+
+impl MediaControls {
+    /// Create media controls with the specified config, using the default backend of the
+    /// platform.
+    ///
+    /// On Linux, the default is the D-Bus backend if the `use_dbus` feature is enabled, and
+    /// the zbus backend otherwise.
+    pub fn new(config: PlatformConfig) -> Result<Self, Error> {
+        Ok(Self::from_backend(DefaultBackend::new(config)?))
+    }
+
+    /// Create media controls which use the given backend, e.g. to choose one at runtime.
+    pub fn from_backend<B>(backend: B) -> Self
+    where
+        B: MediaControlsBackend + 'static,
+    {
+        Self {
+            backend: Box::new(backend),
+        }
+    }
+
+    /// Attach the media control events to a handler.
+    ///
+    /// The handler may return a `Result<(), EventError>` to report the events it couldn't
+    /// carry out, see [`EventError`].
+    ///
+    /// If the handler panics, the panic is caught and the event fails with
+    /// [`EventError::Failed`], carrying the panic message. The handler keeps receiving the
+    /// next events, so it must not be left in a broken state by a panic.
+    ///
+    /// On MPRIS, the controls reconnect by themselves if the connection to the session bus
+    /// is lost, and the handler receives [`MediaControlEvent::Disconnected`] and
+    /// [`MediaControlEvent::Reconnected`] meanwhile.
+    pub fn attach<F, R>(&mut self, event_handler: F) -> Result<(), Error>
+    where
+        F: FnMut(MediaControlEvent) -> R + Send + 'static,
+        R: EventResult,
+    {
+        self.backend.attach(box_event_handler(event_handler))
+    }
+
+    /// Detach the event handler.
+    pub fn detach(&mut self) -> Result<(), Error> {
+        self.backend.detach()
+    }
+
+    /// Set the current playback status.
+    ///
+    /// On MPRIS, the reported position advances on its own while playing, at the current
+    /// rate and up to the duration of the track. The progress only needs to be set again when
+    /// it jumps, e.g. after a seek.
+    pub fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+        self.backend.set_playback(playback)
+    }
+
+    /// Set the metadata of the currently playing media item, either as a [`MediaMetadata`] or
+    /// as an [`OwnedMediaMetadata`].
+    pub fn set_metadata<M>(&mut self, metadata: M) -> Result<(), Error>
+    where
+        M: Into<OwnedMediaMetadata>,
+    {
+        self.backend.set_metadata(metadata.into())
+    }
+
+    /// Change some fields of the metadata, and keep the others. Nothing is published if the
+    /// metadata doesn't change.
+    ///
+    /// On MPRIS, a generated `track_id` is kept, unless the patch sets another one.
+    pub fn update_metadata(&mut self, patch: MetadataPatch) -> Result<(), Error> {
+        self.backend.update_metadata(patch)
+    }
+
+    /// Set the volume level (0.0-1.0) (Only used on MPRIS)
+    pub fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+        self.backend.set_volume(volume)
+    }
+
+    /// Set whether shuffle is enabled.
+    pub fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.backend.set_shuffle(shuffle)
+    }
+
+    /// Set the loop/repeat status.
+    pub fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        self.backend.set_loop_status(loop_status)
+    }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    pub fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.backend.set_rate(rate)
+    }
+
+    /// Set the range of playback rates that clients may request. The range must include
+    /// 1.0. By default only 1.0 is allowed. (Only used on MPRIS)
+    pub fn set_rate_range(&mut self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        self.backend.set_rate_range(minimum, maximum)
+    }
+
+    /// Set whether the media player is in fullscreen. Clients may only request changes if
+    /// [`Capabilities::can_set_fullscreen`] is `true`. (Only used on MPRIS)
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Error> {
+        self.backend.set_fullscreen(fullscreen)
+    }
+
+    /// Notify clients that the position jumped to `position`, e.g. after a seek, and update
+    /// the reported position accordingly. (Only used on MPRIS)
+    pub fn notify_seeked(&mut self, position: MediaPosition) -> Result<(), Error> {
+        self.backend.notify_seeked(position)
+    }
+
+    /// Set the actions supported by the media player.
+    pub fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.backend.set_capabilities(capabilities)
+    }
+
+    /// Replace the whole tracklist, optionally specifying the id of the current track.
+    /// (Only used on MPRIS)
+    ///
+    /// Each track is given along with its [`TrackId`](crate::TrackId), which must be a valid
+    /// D-Bus object path, and unique within the tracklist.
+    pub fn set_tracklist(
+        &mut self,
+        tracks: &[(&str, MediaMetadata)],
+        current: Option<&str>,
+    ) -> Result<(), Error> {
+        self.backend.set_tracklist(tracks, current)
+    }
+
+    /// Insert a track into the tracklist, right after the track `after`.
+    /// If `after` is `None`, the track is inserted at the beginning. (Only used on MPRIS)
+    ///
+    /// Returns an error if the tracklist already has a track `id`, or has no track `after`.
+    pub fn insert_track(
+        &mut self,
+        id: &str,
+        metadata: MediaMetadata,
+        after: Option<&str>,
+    ) -> Result<(), Error> {
+        self.backend.insert_track(id, metadata, after)
+    }
+
+    /// Remove a track from the tracklist. Returns an error if it isn't in the tracklist.
+    /// (Only used on MPRIS)
+    pub fn remove_track(&mut self, id: &str) -> Result<(), Error> {
+        self.backend.remove_track(id)
+    }
+
+    /// Set the metadata of a track in the tracklist. Returns an error if it isn't in the
+    /// tracklist. (Only used on MPRIS)
+    pub fn set_track_metadata(&mut self, id: &str, metadata: MediaMetadata) -> Result<(), Error> {
+        self.backend.set_track_metadata(id, metadata)
+    }
+
+    /// Set the playlists which can be activated by the user. (Only used on MPRIS)
+    pub fn set_playlists(&mut self, playlists: &[MediaPlaylist]) -> Result<(), Error> {
+        self.backend.set_playlists(playlists)
+    }
+
+    /// Set the id of the currently active playlist, or `None` if there isn't one.
+    /// (Only used on MPRIS)
+    pub fn set_active_playlist(&mut self, id: Option<&str>) -> Result<(), Error> {
+        self.backend.set_active_playlist(id)
+    }
+
+    /// Set the orderings in which the playlists can be listed. (Only used on MPRIS)
+    ///
+    /// Only [`PlaylistOrdering::Alphabetical`] is applied by souvlaki. Any other ordering keeps
+    /// the order in which the playlists were set, so they should be set in user-defined order.
+    /// Defaults to [`PlaylistOrdering::UserDefined`].
+    pub fn set_playlist_orderings(&mut self, orderings: &[PlaylistOrdering]) -> Result<(), Error> {
+        self.backend.set_playlist_orderings(orderings)
+    }
+
+    /// The metadata currently published, as set by the last successful call to
+    /// [`set_metadata`](Self::set_metadata).
+    ///
+    /// On MPRIS, `track_id` is the id which is actually published, even if it was generated.
+    pub fn metadata(&self) -> OwnedMediaMetadata {
+        self.backend.metadata()
+    }
+
+    /// The playback status currently published.
+    ///
+    /// On MPRIS, the progress is extrapolated to the current time, as clients see it.
+    pub fn playback(&self) -> MediaPlayback {
+        self.backend.playback()
+    }
+
+    /// The volume level currently published. (Only used on MPRIS, elsewhere it's always 1.0)
+    pub fn volume(&self) -> f64 {
+        self.backend.volume()
+    }
+
+    /// The actions currently published as supported.
+    ///
+    /// On MPRIS, the playback capabilities are `false` if `can_control` is `false`.
+    pub fn capabilities(&self) -> Capabilities {
+        self.backend.capabilities()
+    }
+}
+
+impl Drop for MediaControls {
+    fn drop(&mut self) {
+        // Ignores errors if there are any.
+        self.detach().ok();
+    }
+}
+
+impl Debug for MediaControls {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MediaControls")?;
+        Ok(())
+    }
+}
@@ -1,12 +1,38 @@
 #![doc = include_str!("../README.md")]
 
 mod config;
+mod controls;
 mod platform;
 
-use std::{fmt::Debug, time::Duration};
+use std::time::Duration;
 
 pub use config::*;
-pub use platform::{Error, MediaControls};
+pub use controls::*;
+#[cfg(all(
+    feature = "async",
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+pub use platform::AsyncMediaControls;
+#[cfg(all(
+    feature = "dbus",
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+pub use platform::DbusBackend;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+pub use platform::MacosBackend;
+#[cfg(target_os = "windows")]
+pub use platform::WindowsBackend;
+#[cfg(all(
+    feature = "zbus",
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+pub use platform::ZbusBackend;
+pub use platform::{Error, NoopBackend};
+#[cfg(feature = "mock")]
+pub use platform::{MockBackend, MockCall, MockState};
 
 /// The status of media playback.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -17,8 +43,16 @@ pub enum MediaPlayback {
 }
 
 /// The metadata of a media item.
-#[derive(Clone, PartialEq, Eq, Debug, Default)]
+///
+/// Platforms which can't show a field ignore it.
+#[derive(Clone, PartialEq, Debug, Default)]
 pub struct MediaMetadata<'a> {
+    /// An identifier which is unique to this media item.
+    ///
+    /// On MPRIS, this must be a valid D-Bus object path (see [`TrackId`]). It is used to ignore
+    /// stale `SetPosition` requests, which were meant for a previous track. If it's `None`, a new
+    /// id is generated every time the metadata changes.
+    pub track_id: Option<&'a str>,
     pub title: Option<&'a str>,
     pub album: Option<&'a str>,
     pub artist: Option<&'a str>,
@@ -31,6 +65,412 @@ pub struct MediaMetadata<'a> {
     /// For MacOS, you can look into [these lines](https://github.com/Sinono3/souvlaki/blob/384539fe83e8bf5c966192ba28e9405e3253619b/src/platform/macos/mod.rs#L131-L137) of the implementation. These lines refer to creating an [MPMediaItemArtwork](https://developer.apple.com/documentation/mediaplayer/mpmediaitemartwork) object.
     pub cover_url: Option<&'a str>,
     pub duration: Option<Duration>,
+    /// All the artists of the media item. If it's empty, `artist` is used instead.
+    ///
+    /// Platforms which only show one artist use `artist`, or these joined with commas if
+    /// `artist` is `None`.
+    pub artists: &'a [&'a str],
+    pub album_artists: &'a [&'a str],
+    pub composers: &'a [&'a str],
+    pub lyricists: &'a [&'a str],
+    pub genres: &'a [&'a str],
+    pub comments: &'a [&'a str],
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    /// The tempo, in beats per minute.
+    pub audio_bpm: Option<i32>,
+    /// When the media item was created, e.g. its release date, as an ISO 8601 date/time.
+    pub content_created: Option<&'a str>,
+    /// When the media item was first played, as an ISO 8601 date/time.
+    pub first_used: Option<&'a str>,
+    /// When the media item was last played, as an ISO 8601 date/time.
+    pub last_used: Option<&'a str>,
+    /// How many times the media item has been played.
+    pub use_count: Option<i32>,
+    /// A rating given by the user, from 0.0 to 1.0.
+    pub user_rating: Option<f64>,
+    /// A rating computed by the media player, e.g. from the play count, from 0.0 to 1.0.
+    pub auto_rating: Option<f64>,
+    /// The location of the media file.
+    pub url: Option<&'a str>,
+    /// The lyrics, or another text representation of the media item.
+    pub lyrics: Option<&'a str>,
+    /// Extra entries which aren't part of the standard vocabulary. Keys must follow the
+    /// `namespace:key` convention, e.g. `myplayer:trackSource`, and can't use the `mpris` and
+    /// `xesam` namespaces, which are reserved for the fields above. (Only used on MPRIS)
+    pub custom: &'a [(&'a str, MetadataValue<'a>)],
+}
+
+/// The value of a custom metadata entry.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MetadataValue<'a> {
+    String(&'a str),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    StringList(&'a [&'a str]),
+}
+
+/// An owned version of [`MediaMetadata`], e.g. to keep the current track in a struct or to
+/// send it to another thread. It can be passed to
+/// [`MediaControls::set_metadata`] directly, and is returned by [`MediaControls::metadata`].
+///
+/// With the `serde` feature, it implements `Serialize` and `Deserialize`.
+#[derive(Clone, PartialEq, Debug, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default)
+)]
+pub struct OwnedMediaMetadata {
+    pub track_id: Option<String>,
+    pub title: Option<String>,
+    pub album: Option<String>,
+    pub artist: Option<String>,
+    pub cover_url: Option<String>,
+    pub duration: Option<Duration>,
+    pub artists: Vec<String>,
+    pub album_artists: Vec<String>,
+    pub composers: Vec<String>,
+    pub lyricists: Vec<String>,
+    pub genres: Vec<String>,
+    pub comments: Vec<String>,
+    pub track_number: Option<i32>,
+    pub disc_number: Option<i32>,
+    pub audio_bpm: Option<i32>,
+    pub content_created: Option<String>,
+    pub first_used: Option<String>,
+    pub last_used: Option<String>,
+    pub use_count: Option<i32>,
+    pub user_rating: Option<f64>,
+    pub auto_rating: Option<f64>,
+    pub url: Option<String>,
+    pub lyrics: Option<String>,
+    pub custom: Vec<(String, OwnedMetadataValue)>,
+}
+
+impl From<&MediaMetadata<'_>> for OwnedMediaMetadata {
+    fn from(other: &MediaMetadata) -> Self {
+        let to_string = |s: Option<&str>| s.map(str::to_string);
+        let to_strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+
+        OwnedMediaMetadata {
+            track_id: to_string(other.track_id),
+            title: to_string(other.title),
+            album: to_string(other.album),
+            artist: to_string(other.artist),
+            cover_url: to_string(other.cover_url),
+            duration: other.duration,
+            artists: to_strings(other.artists),
+            album_artists: to_strings(other.album_artists),
+            composers: to_strings(other.composers),
+            lyricists: to_strings(other.lyricists),
+            genres: to_strings(other.genres),
+            comments: to_strings(other.comments),
+            track_number: other.track_number,
+            disc_number: other.disc_number,
+            audio_bpm: other.audio_bpm,
+            content_created: to_string(other.content_created),
+            first_used: to_string(other.first_used),
+            last_used: to_string(other.last_used),
+            use_count: other.use_count,
+            user_rating: other.user_rating,
+            auto_rating: other.auto_rating,
+            url: to_string(other.url),
+            lyrics: to_string(other.lyrics),
+            custom: other
+                .custom
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<MediaMetadata<'_>> for OwnedMediaMetadata {
+    fn from(other: MediaMetadata) -> Self {
+        Self::from(&other)
+    }
+}
+
+impl From<&OwnedMediaMetadata> for OwnedMediaMetadata {
+    fn from(other: &OwnedMediaMetadata) -> Self {
+        other.clone()
+    }
+}
+
+impl OwnedMediaMetadata {
+    /// Calls `f` with a [`MediaMetadata`] which borrows from this metadata.
+    ///
+    /// `MediaMetadata` holds slices of `&str`, so they are collected for the duration
+    /// of the call.
+    pub fn with_borrowed<R>(&self, f: impl FnOnce(MediaMetadata) -> R) -> R {
+        fn strs(list: &[String]) -> Vec<&str> {
+            list.iter().map(String::as_str).collect()
+        }
+
+        let artists = strs(&self.artists);
+        let album_artists = strs(&self.album_artists);
+        let composers = strs(&self.composers);
+        let lyricists = strs(&self.lyricists);
+        let genres = strs(&self.genres);
+        let comments = strs(&self.comments);
+        let custom_lists: Vec<Vec<&str>> = self
+            .custom
+            .iter()
+            .map(|(_, value)| match value {
+                OwnedMetadataValue::StringList(list) => strs(list),
+                _ => Vec::new(),
+            })
+            .collect();
+        let custom: Vec<(&str, MetadataValue)> = self
+            .custom
+            .iter()
+            .zip(&custom_lists)
+            .map(|((key, value), list)| {
+                let value = match value {
+                    OwnedMetadataValue::String(value) => MetadataValue::String(value),
+                    OwnedMetadataValue::Integer(value) => MetadataValue::Integer(*value),
+                    OwnedMetadataValue::Float(value) => MetadataValue::Float(*value),
+                    OwnedMetadataValue::Bool(value) => MetadataValue::Bool(*value),
+                    OwnedMetadataValue::StringList(_) => MetadataValue::StringList(list),
+                };
+                (key.as_str(), value)
+            })
+            .collect();
+
+        f(MediaMetadata {
+            track_id: self.track_id.as_deref(),
+            title: self.title.as_deref(),
+            album: self.album.as_deref(),
+            artist: self.artist.as_deref(),
+            cover_url: self.cover_url.as_deref(),
+            duration: self.duration,
+            artists: &artists,
+            album_artists: &album_artists,
+            composers: &composers,
+            lyricists: &lyricists,
+            genres: &genres,
+            comments: &comments,
+            track_number: self.track_number,
+            disc_number: self.disc_number,
+            audio_bpm: self.audio_bpm,
+            content_created: self.content_created.as_deref(),
+            first_used: self.first_used.as_deref(),
+            last_used: self.last_used.as_deref(),
+            use_count: self.use_count,
+            user_rating: self.user_rating,
+            auto_rating: self.auto_rating,
+            url: self.url.as_deref(),
+            lyrics: self.lyrics.as_deref(),
+            custom: &custom,
+        })
+    }
+}
+
+/// An owned version of [`MetadataValue`].
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OwnedMetadataValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    StringList(Vec<String>),
+}
+
+impl From<&MetadataValue<'_>> for OwnedMetadataValue {
+    fn from(other: &MetadataValue) -> Self {
+        match *other {
+            MetadataValue::String(value) => OwnedMetadataValue::String(value.to_string()),
+            MetadataValue::Integer(value) => OwnedMetadataValue::Integer(value),
+            MetadataValue::Float(value) => OwnedMetadataValue::Float(value),
+            MetadataValue::Bool(value) => OwnedMetadataValue::Bool(value),
+            MetadataValue::StringList(list) => {
+                OwnedMetadataValue::StringList(list.iter().map(|s| s.to_string()).collect())
+            }
+        }
+    }
+}
+
+/// A change to the metadata, applied with [`MediaControls::update_metadata`]. Every field
+/// is kept unless it's set or cleared, and the same changes are published on every platform.
+///
+/// ```
+/// # use souvlaki::{FieldPatch, MetadataPatch};
+/// let patch = MetadataPatch {
+///     title: FieldPatch::Set("Live version".to_string()),
+///     album: FieldPatch::Clear,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MetadataPatch {
+    pub track_id: FieldPatch<String>,
+    pub title: FieldPatch<String>,
+    pub album: FieldPatch<String>,
+    pub artist: FieldPatch<String>,
+    pub cover_url: FieldPatch<String>,
+    pub duration: FieldPatch<Duration>,
+    pub artists: FieldPatch<Vec<String>>,
+    pub album_artists: FieldPatch<Vec<String>>,
+    pub composers: FieldPatch<Vec<String>>,
+    pub lyricists: FieldPatch<Vec<String>>,
+    pub genres: FieldPatch<Vec<String>>,
+    pub comments: FieldPatch<Vec<String>>,
+    pub track_number: FieldPatch<i32>,
+    pub disc_number: FieldPatch<i32>,
+    pub audio_bpm: FieldPatch<i32>,
+    pub content_created: FieldPatch<String>,
+    pub first_used: FieldPatch<String>,
+    pub last_used: FieldPatch<String>,
+    pub use_count: FieldPatch<i32>,
+    pub user_rating: FieldPatch<f64>,
+    pub auto_rating: FieldPatch<f64>,
+    pub url: FieldPatch<String>,
+    pub lyrics: FieldPatch<String>,
+    /// Replaces all the custom entries at once.
+    pub custom: FieldPatch<Vec<(String, OwnedMetadataValue)>>,
+}
+
+/// How a [`MetadataPatch`] changes one field.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum FieldPatch<T> {
+    /// Leave the field as it is.
+    #[default]
+    Keep,
+    /// Replace the field with this value.
+    Set(T),
+    /// Remove the field, or empty it if it's a list.
+    Clear,
+}
+
+impl<T> FieldPatch<T> {
+    fn apply(self, field: &mut Option<T>) {
+        match self {
+            FieldPatch::Keep => {}
+            FieldPatch::Set(value) => *field = Some(value),
+            FieldPatch::Clear => *field = None,
+        }
+    }
+}
+
+impl<T> FieldPatch<Vec<T>> {
+    fn apply_list(self, field: &mut Vec<T>) {
+        match self {
+            FieldPatch::Keep => {}
+            FieldPatch::Set(value) => *field = value,
+            FieldPatch::Clear => field.clear(),
+        }
+    }
+}
+
+impl MetadataPatch {
+    /// Applies the changes to `metadata`.
+    pub fn apply(self, metadata: &mut OwnedMediaMetadata) {
+        self.track_id.apply(&mut metadata.track_id);
+        self.title.apply(&mut metadata.title);
+        self.album.apply(&mut metadata.album);
+        self.artist.apply(&mut metadata.artist);
+        self.cover_url.apply(&mut metadata.cover_url);
+        self.duration.apply(&mut metadata.duration);
+        self.artists.apply_list(&mut metadata.artists);
+        self.album_artists.apply_list(&mut metadata.album_artists);
+        self.composers.apply_list(&mut metadata.composers);
+        self.lyricists.apply_list(&mut metadata.lyricists);
+        self.genres.apply_list(&mut metadata.genres);
+        self.comments.apply_list(&mut metadata.comments);
+        self.track_number.apply(&mut metadata.track_number);
+        self.disc_number.apply(&mut metadata.disc_number);
+        self.audio_bpm.apply(&mut metadata.audio_bpm);
+        self.content_created.apply(&mut metadata.content_created);
+        self.first_used.apply(&mut metadata.first_used);
+        self.last_used.apply(&mut metadata.last_used);
+        self.use_count.apply(&mut metadata.use_count);
+        self.user_rating.apply(&mut metadata.user_rating);
+        self.auto_rating.apply(&mut metadata.auto_rating);
+        self.url.apply(&mut metadata.url);
+        self.lyrics.apply(&mut metadata.lyrics);
+        self.custom.apply_list(&mut metadata.custom);
+    }
+
+    /// Returns `metadata` with the changes applied, or `None` if they don't change it.
+    pub(crate) fn applied_to(self, metadata: &OwnedMediaMetadata) -> Option<OwnedMediaMetadata> {
+        let mut patched = metadata.clone();
+        self.apply(&mut patched);
+        if patched == *metadata {
+            None
+        } else {
+            Some(patched)
+        }
+    }
+}
+
+/// A playlist which can be activated by the user.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct MediaPlaylist<'a> {
+    /// The identifier of the playlist. On MPRIS this must be a valid D-Bus object path.
+    pub id: &'a str,
+    /// The name of the playlist, as it will be shown to the user.
+    pub name: &'a str,
+    /// The URI of an image associated with the playlist.
+    pub icon: Option<&'a str>,
+}
+
+/// An order in which playlists can be listed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlaylistOrdering {
+    /// Alphabetical ordering by name.
+    Alphabetical,
+    /// Ordering by creation date, oldest first.
+    CreationDate,
+    /// Ordering by last modified date, oldest first.
+    ModifiedDate,
+    /// Ordering by date of last playback, oldest first.
+    LastPlayDate,
+    /// A user-defined ordering.
+    UserDefined,
+}
+
+/// The actions supported by the media player. The OS hides or disables the controls of
+/// unsupported actions, and requests for them are ignored where possible.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    /// Whether the playback can be controlled at all. If `false`, the playback capabilities
+    /// (`can_go_next`, `can_go_previous`, `can_play`, `can_pause` and `can_seek`) are
+    /// treated as `false` too.
+    pub can_control: bool,
+    pub can_go_next: bool,
+    pub can_go_previous: bool,
+    pub can_play: bool,
+    pub can_pause: bool,
+    /// Whether seeking is supported. Usually `false` for live streams.
+    pub can_seek: bool,
+    /// Whether the media player can be shut down. (Only used on MPRIS)
+    pub can_quit: bool,
+    /// Whether the media player can be brought to the front. (Only used on MPRIS)
+    pub can_raise: bool,
+    /// Whether the media player can be put in or out of fullscreen. (Only used on MPRIS)
+    pub can_set_fullscreen: bool,
+    /// Whether tracks can be added to and removed from the tracklist. (Only used on MPRIS)
+    pub can_edit_tracks: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self {
+            can_control: true,
+            can_go_next: true,
+            can_go_previous: true,
+            can_play: true,
+            can_pause: true,
+            can_seek: true,
+            can_quit: true,
+            can_raise: true,
+            can_set_fullscreen: false,
+            can_edit_tracks: true,
+        }
+    }
 }
 
 /// Events sent by the OS media controls.
@@ -57,35 +497,151 @@ pub enum MediaControlEvent {
     /// this must be done only with the MPRIS backend.
     SetVolume(f64),
     /// Open the URI in the media player.
+    ///
+    /// On MPRIS, if [`PlatformConfig::supported_uri_schemes`] isn't empty, only URIs with one
+    /// of its schemes are sent. Likewise for the MIME types of `file://` URIs, guessed from
+    /// their extension, and [`PlatformConfig::supported_mime_types`]. The MIME type of the
+    /// other URIs isn't known, so the handler should reply [`EventError::NotSupported`] if it
+    /// can't open them.
     OpenUri(String),
+    /// Enable or disable shuffle.
+    /// **NOTE**: If the event was correctly handled, the user must call
+    /// [`MediaControls::set_shuffle`] to confirm the change.
+    SetShuffle(bool),
+    /// Set the loop/repeat status.
+    /// **NOTE**: If the event was correctly handled, the user must call
+    /// [`MediaControls::set_loop_status`] to confirm the change.
+    SetLoopStatus(LoopStatus),
+    /// Set the playback rate, where 1.0 is the normal speed. Only rates within the
+    /// range allowed by the backend are sent.
+    /// **NOTE**: If the event was correctly handled, the user must call
+    /// [`MediaControls::set_rate`] to confirm the change.
+    SetRate(f64),
+
+    /// Add a URI to the tracklist, right after the track `after`.
+    /// If `after` is `None`, the track should be inserted at the beginning of the tracklist.
+    /// The URI is checked like the one of [`OpenUri`](Self::OpenUri).
+    AddTrack {
+        uri: String,
+        after: Option<TrackId>,
+        set_as_current: bool,
+    },
+    /// Remove a track from the tracklist.
+    RemoveTrack(TrackId),
+    /// Skip to a track in the tracklist.
+    GoTo(TrackId),
+    /// Start playing the given playlist.
+    ActivatePlaylist(PlaylistId),
 
     /// Bring the media player's user interface to the front using any appropriate mechanism available.
     Raise,
     /// Shut down the media player.
     Quit,
+    /// Put the media player in or out of fullscreen. (Only sent on MPRIS)
+    /// **NOTE**: If the event was correctly handled, the user must call
+    /// [`MediaControls::set_fullscreen`] to confirm the change.
+    SetFullscreen(bool),
+
+    /// The media controls lost their D-Bus name to another application, and are hidden
+    /// until they get it back. (Only sent on MPRIS)
+    NameLost,
+    /// The media controls got their D-Bus name back after losing it. (Only sent on MPRIS)
+    NameAcquired,
+    /// The connection to the session bus was lost. The media controls are hidden while
+    /// they try to reconnect, and the updates made meanwhile are published once they do.
+    /// (Only sent on MPRIS)
+    Disconnected,
+    /// The media controls reconnected to the session bus, and publish their current state
+    /// again. (Only sent on MPRIS)
+    Reconnected,
+}
+
+/// Why the application couldn't carry out a [`MediaControlEvent`], as returned by the
+/// event handler.
+///
+/// On MPRIS, it's sent back to the client as a D-Bus error. On macOS, it becomes the status
+/// of the remote command. Windows has no way to report it, so it's ignored there.
+///
+/// The zbus backend can't name the errors of property changes (volume, shuffle, loop status
+/// and rate): they are all sent as `org.freedesktop.zbus.Error`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EventError {
+    /// The request isn't supported, e.g. a URI with an unknown scheme.
+    ///
+    /// On MPRIS it's sent as `org.freedesktop.DBus.Error.NotSupported`.
+    NotSupported,
+    /// There is nothing to act on, e.g. a `Next` request at the end of the queue.
+    ///
+    /// On MPRIS it's sent as `org.mpris.MediaPlayer2.Error.NoSuchContent`.
+    NoSuchContent,
+    /// The request failed, for the given reason.
+    ///
+    /// On MPRIS it's sent as `org.mpris.MediaPlayer2.Error.Failed`.
+    Failed(String),
+}
+
+impl std::fmt::Display for EventError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EventError::NotSupported => write!(f, "the request is not supported"),
+            EventError::NoSuchContent => write!(f, "there is nothing to act on"),
+            EventError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+/// What an event handler can return: either `()`, if the events always succeed, or a
+/// `Result<(), EventError>`.
+pub trait EventResult {
+    fn into_result(self) -> Result<(), EventError>;
+}
+
+impl EventResult for () {
+    fn into_result(self) -> Result<(), EventError> {
+        Ok(())
+    }
+}
+
+impl EventResult for Result<(), EventError> {
+    fn into_result(self) -> Result<(), EventError> {
+        self
+    }
 }
 
 /// An instant in a media item.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub struct MediaPosition(pub Duration);
 
+/// The identifier of a track in the tracklist.
+///
+/// On MPRIS, this must be a valid D-Bus object path which is not inside the reserved
+/// `/org/mpris` namespace, e.g. `/com/example/track/42`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct TrackId(pub String);
+
+/// The identifier of a playlist.
+///
+/// On MPRIS, this is the object path given in [`MediaPlaylist::id`].
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PlaylistId(pub String);
+
+/// Whether and how the playback loops.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LoopStatus {
+    /// The playback stops when there are no more tracks to play.
+    #[default]
+    None,
+    /// The current track loops.
+    Track,
+    /// The playback loops through the whole playlist.
+    Playlist,
+}
+
 /// The direction to seek in.
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum SeekDirection {
     Forward,
     Backward,
 }
-
-impl Drop for MediaControls {
-    fn drop(&mut self) {
-        // Ignores errors if there are any.
-        self.detach().ok();
-    }
-}
-
-impl Debug for MediaControls {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("MediaControls")?;
-        Ok(())
-    }
-}
@@ -7,7 +7,7 @@ use std::fs;
 use std::{
     sync::{
         atomic::{AtomicUsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
     time::Duration,
 };
@@ -22,7 +22,10 @@ use core_graphics::geometry::CGSize;
 use dispatch::{Queue, QueuePriority};
 use objc::{class, msg_send, sel, sel_impl};
 
-use crate::{MediaControlEvent, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig};
+use crate::{
+    BoxedEventHandler, Capabilities, EventError, LoopStatus, MediaControlEvent,
+    MediaControlsBackend, MediaPlayback, MediaPosition, OwnedMediaMetadata, PlatformConfig,
+};
 
 /// A platform-specific error.
 #[derive(Debug)]
@@ -36,41 +39,97 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
-/// A handle to OS media controls.
-pub struct MediaControls;
+/// The backend built on the `MPRemoteCommandCenter` and `MPNowPlayingInfoCenter` of
+/// macOS and iOS.
+pub struct MacosBackend {
+    capabilities: Capabilities,
+    metadata: OwnedMediaMetadata,
+    playback: MediaPlayback,
+}
+
+/// The backend used by [`MediaControls::new`](crate::MediaControls::new).
+pub(crate) type DefaultBackend = MacosBackend;
 
-impl MediaControls {
+impl MacosBackend {
     /// Create media controls with the specified config.
-    pub fn new(_config: PlatformConfig) -> Result<Self, Error> {
-        Ok(Self)
+    pub fn new(config: PlatformConfig) -> Result<Self, Error> {
+        Ok(Self {
+            capabilities: config.capabilities,
+            metadata: OwnedMediaMetadata::default(),
+            playback: MediaPlayback::Stopped,
+        })
     }
+}
 
+impl MediaControlsBackend for MacosBackend {
     /// Attach the media control events to a handler.
-    pub fn attach<F>(&mut self, event_handler: F) -> Result<(), Error>
-    where
-        F: Fn(MediaControlEvent) + Send + 'static,
-    {
-        unsafe { attach_command_handlers(Arc::new(event_handler)) };
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error> {
+        unsafe {
+            attach_command_handlers(Arc::new(Mutex::new(event_handler)));
+            set_enabled_commands(self.capabilities);
+        }
         Ok(())
     }
 
     /// Detach the event handler.
-    pub fn detach(&mut self) -> Result<(), Error> {
+    fn detach(&mut self) -> Result<(), Error> {
         unsafe { detach_command_handlers() };
         Ok(())
     }
 
     /// Set the current playback status.
-    pub fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+        self.playback = playback.clone();
         unsafe { set_playback_status(playback) };
         Ok(())
     }
 
     /// Set the metadata of the currently playing media item.
-    pub fn set_metadata(&mut self, metadata: MediaMetadata) -> Result<(), Error> {
-        unsafe { set_playback_metadata(metadata) };
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        unsafe { set_playback_metadata(&metadata) };
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    /// Set whether shuffle is enabled.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        unsafe { set_shuffle_type(shuffle) };
+        Ok(())
+    }
+
+    /// Set the loop/repeat status.
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        unsafe { set_repeat_type(loop_status) };
         Ok(())
     }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        unsafe { set_playback_rate(rate) };
+        Ok(())
+    }
+
+    /// Set the actions supported by the media player.
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.capabilities = capabilities;
+        unsafe { set_enabled_commands(capabilities) };
+        Ok(())
+    }
+
+    /// The metadata currently published.
+    fn metadata(&self) -> OwnedMediaMetadata {
+        self.metadata.clone()
+    }
+
+    /// The playback status currently published.
+    fn playback(&self) -> MediaPlayback {
+        self.playback.clone()
+    }
+
+    /// The actions currently published as supported.
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
 }
 
 // MPNowPlayingPlaybackState
@@ -80,14 +139,32 @@ const MPNowPlayingPlaybackStateStopped: NSUInteger = 3;
 
 // MPRemoteCommandHandlerStatus
 const MPRemoteCommandHandlerStatusSuccess: NSInteger = 0;
+const MPRemoteCommandHandlerStatusNoSuchContent: NSInteger = 100;
+const MPRemoteCommandHandlerStatusCommandFailed: NSInteger = 200;
+
+// MPShuffleType
+const MPShuffleTypeOff: NSInteger = 0;
+const MPShuffleTypeItems: NSInteger = 1;
+
+// MPRepeatType
+const MPRepeatTypeOff: NSInteger = 0;
+const MPRepeatTypeOne: NSInteger = 1;
+const MPRepeatTypeAll: NSInteger = 2;
 
 extern "C" {
     static MPMediaItemPropertyTitle: id; // NSString
     static MPMediaItemPropertyArtist: id; // NSString
     static MPMediaItemPropertyAlbumTitle: id; // NSString
+    static MPMediaItemPropertyAlbumArtist: id; // NSString
+    static MPMediaItemPropertyComposer: id; // NSString
+    static MPMediaItemPropertyGenre: id; // NSString
+    static MPMediaItemPropertyAlbumTrackNumber: id; // NSString
+    static MPMediaItemPropertyDiscNumber: id; // NSString
+    static MPMediaItemPropertyPlayCount: id; // NSString
     static MPMediaItemPropertyArtwork: id; // NSString
     static MPMediaItemPropertyPlaybackDuration: id; // NSString
     static MPNowPlayingInfoPropertyElapsedPlaybackTime: id; // NSString
+    static MPNowPlayingInfoPropertyPlaybackRate: id; // NSString
 }
 
 unsafe fn set_playback_status(playback: MediaPlayback) {
@@ -111,32 +188,69 @@ unsafe fn set_playback_status(playback: MediaPlayback) {
 
 static GLOBAL_METADATA_COUNTER: AtomicUsize = AtomicUsize::new(1);
 
-unsafe fn set_playback_metadata(metadata: MediaMetadata) {
+unsafe fn set_playback_metadata(metadata: &OwnedMediaMetadata) {
     let prev_counter = GLOBAL_METADATA_COUNTER.fetch_add(1, Ordering::SeqCst);
     let media_center: id = msg_send!(class!(MPNowPlayingInfoCenter), defaultCenter);
     let now_playing: id = msg_send!(class!(NSMutableDictionary), dictionary);
-    if let Some(title) = metadata.title {
+    if let Some(title) = metadata.title.as_deref() {
         let _: () = msg_send!(now_playing, setObject: ns_string(title)
                                               forKey: MPMediaItemPropertyTitle);
     }
-    if let Some(artist) = metadata.artist {
+    if let Some(artist) = metadata.artist.as_deref() {
         let _: () = msg_send!(now_playing, setObject: ns_string(artist)
                                               forKey: MPMediaItemPropertyArtist);
+    } else if !metadata.artists.is_empty() {
+        let _: () = msg_send!(now_playing, setObject: ns_string(&metadata.artists.join(", "))
+                                              forKey: MPMediaItemPropertyArtist);
     }
-    if let Some(album) = metadata.album {
+    if let Some(album) = metadata.album.as_deref() {
         let _: () = msg_send!(now_playing, setObject: ns_string(album)
                                               forKey: MPMediaItemPropertyAlbumTitle);
     }
+    if !metadata.album_artists.is_empty() {
+        let _: () = msg_send!(now_playing, setObject: ns_string(&metadata.album_artists.join(", "))
+                                              forKey: MPMediaItemPropertyAlbumArtist);
+    }
+    if !metadata.composers.is_empty() {
+        let _: () = msg_send!(now_playing, setObject: ns_string(&metadata.composers.join(", "))
+                                              forKey: MPMediaItemPropertyComposer);
+    }
+    if !metadata.genres.is_empty() {
+        let _: () = msg_send!(now_playing, setObject: ns_string(&metadata.genres.join(", "))
+                                              forKey: MPMediaItemPropertyGenre);
+    }
+    if let Some(track_number) = metadata.track_number {
+        let _: () = msg_send!(now_playing, setObject: ns_number(track_number as f64)
+                                              forKey: MPMediaItemPropertyAlbumTrackNumber);
+    }
+    if let Some(disc_number) = metadata.disc_number {
+        let _: () = msg_send!(now_playing, setObject: ns_number(disc_number as f64)
+                                              forKey: MPMediaItemPropertyDiscNumber);
+    }
+    if let Some(use_count) = metadata.use_count {
+        let _: () = msg_send!(now_playing, setObject: ns_number(use_count as f64)
+                                              forKey: MPMediaItemPropertyPlayCount);
+    }
     if let Some(duration) = metadata.duration {
         let _: () = msg_send!(now_playing, setObject: ns_number(duration.as_secs_f64())
                                               forKey: MPMediaItemPropertyPlaybackDuration);
     }
-    if let Some(cover_url) = metadata.cover_url {
+    if let Some(cover_url) = metadata.cover_url.as_deref() {
         let cover_url = cover_url.to_owned();
         Queue::global(QueuePriority::Default).exec_async(move || {
             load_and_set_playback_artwork(cover_url, prev_counter + 1);
         });
     }
+    // Keep the playback rate, since it's usually not changed along with the media item.
+    let prev_now_playing: id = msg_send!(media_center, nowPlayingInfo);
+    if prev_now_playing != nil {
+        let rate: id =
+            msg_send!(prev_now_playing, objectForKey: MPNowPlayingInfoPropertyPlaybackRate);
+        if rate != nil {
+            let _: () = msg_send!(now_playing, setObject: rate
+                                                  forKey: MPNowPlayingInfoPropertyPlaybackRate);
+        }
+    }
     let _: () = msg_send!(media_center, setNowPlayingInfo: now_playing);
 }
 
@@ -168,15 +282,84 @@ unsafe fn set_playback_progress(progress: Duration) {
     let _: () = msg_send!(media_center, setNowPlayingInfo: now_playing);
 }
 
-unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
+unsafe fn set_playback_rate(rate: f64) {
+    let media_center: id = msg_send!(class!(MPNowPlayingInfoCenter), defaultCenter);
+    let now_playing: id = msg_send!(class!(NSMutableDictionary), dictionary);
+    let prev_now_playing: id = msg_send!(media_center, nowPlayingInfo);
+    let _: () = msg_send!(now_playing, addEntriesFromDictionary: prev_now_playing);
+    let _: () = msg_send!(now_playing, setObject: ns_number(rate)
+                                          forKey: MPNowPlayingInfoPropertyPlaybackRate);
+    let _: () = msg_send!(media_center, setNowPlayingInfo: now_playing);
+}
+
+unsafe fn set_shuffle_type(shuffle: bool) {
+    let command_center: id = msg_send!(class!(MPRemoteCommandCenter), sharedCommandCenter);
+    let shuffle_type = if shuffle {
+        MPShuffleTypeItems
+    } else {
+        MPShuffleTypeOff
+    };
+    let cmd: id = msg_send!(command_center, changeShuffleModeCommand);
+    let _: () = msg_send!(cmd, setCurrentShuffleType: shuffle_type);
+}
+
+unsafe fn set_repeat_type(loop_status: LoopStatus) {
+    let command_center: id = msg_send!(class!(MPRemoteCommandCenter), sharedCommandCenter);
+    let repeat_type = match loop_status {
+        LoopStatus::None => MPRepeatTypeOff,
+        LoopStatus::Track => MPRepeatTypeOne,
+        LoopStatus::Playlist => MPRepeatTypeAll,
+    };
+    let cmd: id = msg_send!(command_center, changeRepeatModeCommand);
+    let _: () = msg_send!(cmd, setCurrentRepeatType: repeat_type);
+}
+
+unsafe fn set_enabled_commands(capabilities: Capabilities) {
+    let command_center: id = msg_send!(class!(MPRemoteCommandCenter), sharedCommandCenter);
+    let can_control = capabilities.can_control;
+
+    let cmd: id = msg_send!(command_center, togglePlayPauseCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_pause);
+    let cmd: id = msg_send!(command_center, playCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_play);
+    let cmd: id = msg_send!(command_center, pauseCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_pause);
+    let cmd: id = msg_send!(command_center, previousTrackCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_go_previous);
+    let cmd: id = msg_send!(command_center, nextTrackCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_go_next);
+    let cmd: id = msg_send!(command_center, changePlaybackPositionCommand);
+    set_command_enabled(cmd, can_control && capabilities.can_seek);
+    let cmd: id = msg_send!(command_center, changeShuffleModeCommand);
+    set_command_enabled(cmd, can_control);
+    let cmd: id = msg_send!(command_center, changeRepeatModeCommand);
+    set_command_enabled(cmd, can_control);
+    let cmd: id = msg_send!(command_center, changePlaybackRateCommand);
+    set_command_enabled(cmd, can_control);
+}
+
+unsafe fn set_command_enabled(cmd: id, enabled: bool) {
+    let enabled = if enabled { YES } else { NO };
+    let _: () = msg_send!(cmd, setEnabled: enabled);
+}
+
+/// Converts the result of the event handler to the status returned by a command handler.
+fn command_status(result: Result<(), EventError>) -> NSInteger {
+    match result {
+        Ok(()) => MPRemoteCommandHandlerStatusSuccess,
+        Err(EventError::NoSuchContent) => MPRemoteCommandHandlerStatusNoSuchContent,
+        Err(_) => MPRemoteCommandHandlerStatusCommandFailed,
+    }
+}
+
+unsafe fn attach_command_handlers(handler: Arc<Mutex<BoxedEventHandler>>) {
     let command_center: id = msg_send!(class!(MPRemoteCommandCenter), sharedCommandCenter);
 
     // togglePlayPauseCommand
     let play_pause_handler = ConcreteBlock::new({
         let handler = handler.clone();
         move |_event: id| -> NSInteger {
-            (handler)(MediaControlEvent::Toggle);
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::Toggle))
         }
     })
     .copy();
@@ -188,8 +371,7 @@ unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
     let play_handler = ConcreteBlock::new({
         let handler = handler.clone();
         move |_event: id| -> NSInteger {
-            (handler)(MediaControlEvent::Play);
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::Play))
         }
     })
     .copy();
@@ -201,8 +383,7 @@ unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
     let pause_handler = ConcreteBlock::new({
         let handler = handler.clone();
         move |_event: id| -> NSInteger {
-            (handler)(MediaControlEvent::Pause);
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::Pause))
         }
     })
     .copy();
@@ -214,8 +395,7 @@ unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
     let previous_track_handler = ConcreteBlock::new({
         let handler = handler.clone();
         move |_event: id| -> NSInteger {
-            (handler)(MediaControlEvent::Previous);
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::Previous))
         }
     })
     .copy();
@@ -227,8 +407,7 @@ unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
     let next_track_handler = ConcreteBlock::new({
         let handler = handler.clone();
         move |_event: id| -> NSInteger {
-            (handler)(MediaControlEvent::Next);
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::Next))
         }
     })
     .copy();
@@ -242,16 +421,68 @@ unsafe fn attach_command_handlers(handler: Arc<dyn Fn(MediaControlEvent)>) {
         // event of type MPChangePlaybackPositionCommandEvent
         move |event: id| -> NSInteger {
             let position = *event.as_ref().unwrap().get_ivar::<f64>("_positionTime");
-            (handler)(MediaControlEvent::SetPosition(MediaPosition(
-                Duration::from_secs_f64(position),
-            )));
-            MPRemoteCommandHandlerStatusSuccess
+            command_status((handler.lock().unwrap())(MediaControlEvent::SetPosition(
+                MediaPosition(Duration::from_secs_f64(position)),
+            )))
         }
     })
     .copy();
     let cmd: id = msg_send!(command_center, changePlaybackPositionCommand);
     let _: () = msg_send!(cmd, setEnabled: YES);
     let _: () = msg_send!(cmd, addTargetWithHandler: position_handler);
+
+    // changeShuffleModeCommand
+    let shuffle_handler = ConcreteBlock::new({
+        let handler = handler.clone();
+        // event of type MPChangeShuffleModeCommandEvent
+        move |event: id| -> NSInteger {
+            let shuffle_type: NSInteger = msg_send!(event, shuffleType);
+            command_status((handler.lock().unwrap())(MediaControlEvent::SetShuffle(
+                shuffle_type != MPShuffleTypeOff,
+            )))
+        }
+    })
+    .copy();
+    let cmd: id = msg_send!(command_center, changeShuffleModeCommand);
+    let _: () = msg_send!(cmd, setEnabled: YES);
+    let _: () = msg_send!(cmd, addTargetWithHandler: shuffle_handler);
+
+    // changeRepeatModeCommand
+    let repeat_handler = ConcreteBlock::new({
+        let handler = handler.clone();
+        // event of type MPChangeRepeatModeCommandEvent
+        move |event: id| -> NSInteger {
+            let repeat_type: NSInteger = msg_send!(event, repeatType);
+            let loop_status = match repeat_type {
+                MPRepeatTypeOne => LoopStatus::Track,
+                MPRepeatTypeAll => LoopStatus::Playlist,
+                _ => LoopStatus::None,
+            };
+            command_status((handler.lock().unwrap())(MediaControlEvent::SetLoopStatus(
+                loop_status,
+            )))
+        }
+    })
+    .copy();
+    let cmd: id = msg_send!(command_center, changeRepeatModeCommand);
+    let _: () = msg_send!(cmd, setEnabled: YES);
+    let _: () = msg_send!(cmd, addTargetWithHandler: repeat_handler);
+
+    // changePlaybackRateCommand
+    let rate_handler = ConcreteBlock::new({
+        let handler = handler.clone();
+        // event of type MPChangePlaybackRateCommandEvent
+        move |event: id| -> NSInteger {
+            let rate: f32 = msg_send!(event, playbackRate);
+            command_status((handler.lock().unwrap())(MediaControlEvent::SetRate(
+                rate as f64,
+            )))
+        }
+    })
+    .copy();
+    let cmd: id = msg_send!(command_center, changePlaybackRateCommand);
+    let _: () = msg_send!(cmd, setEnabled: YES);
+    let _: () = msg_send!(cmd, addTargetWithHandler: rate_handler);
 }
 
 unsafe fn detach_command_handlers() {
@@ -280,6 +511,18 @@ unsafe fn detach_command_handlers() {
     let cmd: id = msg_send!(command_center, changePlaybackPositionCommand);
     let _: () = msg_send!(cmd, setEnabled: NO);
     let _: () = msg_send!(cmd, removeTarget: nil);
+
+    let cmd: id = msg_send!(command_center, changeShuffleModeCommand);
+    let _: () = msg_send!(cmd, setEnabled: NO);
+    let _: () = msg_send!(cmd, removeTarget: nil);
+
+    let cmd: id = msg_send!(command_center, changeRepeatModeCommand);
+    let _: () = msg_send!(cmd, setEnabled: NO);
+    let _: () = msg_send!(cmd, removeTarget: nil);
+
+    let cmd: id = msg_send!(command_center, changePlaybackRateCommand);
+    let _: () = msg_send!(cmd, setEnabled: NO);
+    let _: () = msg_send!(cmd, removeTarget: nil);
 }
 
 unsafe fn ns_string(value: &str) -> id {
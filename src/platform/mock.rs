@@ -0,0 +1,215 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::{
+    BoxedEventHandler, Capabilities, Error, EventError, LoopStatus, MediaControlEvent,
+    MediaControlsBackend, MediaPlayback, OwnedMediaMetadata,
+};
+
+/// An in-memory backend for testing applications, available on every platform with the
+/// `mock` feature.
+///
+/// The calls made through [`MediaControls`](crate::MediaControls) are recorded instead of
+/// being published. Clones share the same state, so a test can keep a clone to inspect
+/// the calls and to send events to the attached handler:
+///
+/// ```
+/// # use souvlaki::{MediaControlEvent, MediaControls, MockBackend};
+/// let mock = MockBackend::new();
+/// let mut controls = MediaControls::from_backend(mock.clone());
+/// controls.attach(|event| println!("{:?}", event)).unwrap();
+///
+/// assert!(mock.emit(MediaControlEvent::Play));
+/// ```
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    inner: Arc<Mutex<MockInner>>,
+}
+
+#[derive(Default)]
+struct MockInner {
+    calls: Vec<MockCall>,
+    state: MockState,
+    failures: VecDeque<Error>,
+    event_handler: Option<EventHandler>,
+}
+
+type EventHandler = Arc<Mutex<BoxedEventHandler>>;
+
+/// A call made to a [`MockBackend`].
+// Boxing the metadata would only make assertions harder to write.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, PartialEq, Debug)]
+pub enum MockCall {
+    Attach,
+    Detach,
+    SetPlayback(MediaPlayback),
+    SetMetadata(OwnedMediaMetadata),
+    SetVolume(f64),
+    SetShuffle(bool),
+    SetLoopStatus(LoopStatus),
+    SetRate(f64),
+    SetCapabilities(Capabilities),
+}
+
+/// What a [`MockBackend`] currently publishes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct MockState {
+    /// Whether an event handler is attached.
+    pub attached: bool,
+    pub playback: MediaPlayback,
+    pub metadata: OwnedMediaMetadata,
+    pub volume: f64,
+    pub shuffle: bool,
+    pub loop_status: LoopStatus,
+    pub rate: f64,
+    pub capabilities: Capabilities,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            attached: false,
+            playback: MediaPlayback::Stopped,
+            metadata: OwnedMediaMetadata::default(),
+            volume: 1.0,
+            shuffle: false,
+            loop_status: LoopStatus::None,
+            rate: 1.0,
+            capabilities: Capabilities::default(),
+        }
+    }
+}
+
+impl MockBackend {
+    /// Create a mock backend, with nothing recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The calls made so far, oldest first. Calls which failed are not recorded.
+    pub fn calls(&self) -> Vec<MockCall> {
+        self.inner.lock().unwrap().calls.clone()
+    }
+
+    /// Forget the calls recorded so far.
+    pub fn clear_calls(&self) {
+        self.inner.lock().unwrap().calls.clear();
+    }
+
+    /// What is currently published, as set by the successful calls.
+    pub fn state(&self) -> MockState {
+        self.inner.lock().unwrap().state.clone()
+    }
+
+    /// Send an event to the attached handler, as if it came from the OS.
+    ///
+    /// Returns `false` if no handler is attached.
+    pub fn emit(&self, event: MediaControlEvent) -> bool {
+        self.request(event).is_some()
+    }
+
+    /// Send an event to the attached handler, and return what the handler replied, or
+    /// `None` if no handler is attached.
+    pub fn request(&self, event: MediaControlEvent) -> Option<Result<(), EventError>> {
+        // The lock is released first, so that the handler may call the backend.
+        let event_handler = self.inner.lock().unwrap().event_handler.clone()?;
+        let result = (event_handler.lock().unwrap())(event);
+        Some(result)
+    }
+
+    /// Make the next call fail with `error`, e.g. `Error::ThreadNotRunning` on MPRIS. If
+    /// several failures are queued, each one is returned by one call, in order.
+    ///
+    /// The failing call is neither recorded nor applied.
+    pub fn fail_next_call(&self, error: Error) {
+        self.inner.lock().unwrap().failures.push_back(error);
+    }
+
+    fn call(&mut self, call: MockCall) -> Result<(), Error> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(error) = inner.failures.pop_front() {
+            return Err(error);
+        }
+
+        let state = &mut inner.state;
+        match &call {
+            MockCall::Attach => state.attached = true,
+            MockCall::Detach => state.attached = false,
+            MockCall::SetPlayback(playback) => state.playback = playback.clone(),
+            MockCall::SetMetadata(metadata) => state.metadata = metadata.clone(),
+            MockCall::SetVolume(volume) => state.volume = *volume,
+            MockCall::SetShuffle(shuffle) => state.shuffle = *shuffle,
+            MockCall::SetLoopStatus(loop_status) => state.loop_status = *loop_status,
+            MockCall::SetRate(rate) => state.rate = *rate,
+            MockCall::SetCapabilities(capabilities) => state.capabilities = *capabilities,
+        }
+        inner.calls.push(call);
+        Ok(())
+    }
+}
+
+impl MediaControlsBackend for MockBackend {
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error> {
+        self.call(MockCall::Attach)?;
+        self.inner.lock().unwrap().event_handler = Some(Arc::new(Mutex::new(event_handler)));
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), Error> {
+        self.call(MockCall::Detach)?;
+        self.inner.lock().unwrap().event_handler = None;
+        Ok(())
+    }
+
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+        self.call(MockCall::SetPlayback(playback))
+    }
+
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        self.call(MockCall::SetMetadata(metadata))
+    }
+
+    fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+        self.call(MockCall::SetVolume(volume))
+    }
+
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.call(MockCall::SetShuffle(shuffle))
+    }
+
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        self.call(MockCall::SetLoopStatus(loop_status))
+    }
+
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.call(MockCall::SetRate(rate))
+    }
+
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.call(MockCall::SetCapabilities(capabilities))
+    }
+
+    fn metadata(&self) -> OwnedMediaMetadata {
+        self.inner.lock().unwrap().state.metadata.clone()
+    }
+
+    fn playback(&self) -> MediaPlayback {
+        self.inner.lock().unwrap().state.playback.clone()
+    }
+
+    fn volume(&self) -> f64 {
+        self.inner.lock().unwrap().state.volume
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.inner.lock().unwrap().state.capabilities
+    }
+}
+
+impl std::fmt::Debug for MockBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("MockBackend")?;
+        Ok(())
+    }
+}
@@ -1,6 +1,13 @@
 #![allow(clippy::module_inception)]
+#[cfg(feature = "mock")]
+pub use self::mock::{MockBackend, MockCall, MockState};
+pub use self::noop::NoopBackend;
 pub use self::platform::*;
 
+#[cfg(feature = "mock")]
+mod mock;
+mod noop;
+
 #[cfg(target_os = "windows")]
 #[path = "windows/mod.rs"]
 mod platform;
@@ -0,0 +1,324 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use futures_core::Stream;
+use zbus::export::futures_util::future::{self, Either};
+use zbus::Connection;
+
+use super::state::{InternalEvent, ServiceState};
+use super::zbus::{connect, disconnect, emit, run_with_executor};
+use super::{bus_name, Error, RootProperties};
+use crate::{
+    BoxedEventHandler, Capabilities, DbusConnection, LoopStatus, MediaControlEvent, MediaMetadata,
+    MediaPlayback, MediaPlaylist, MediaPosition, MetadataPatch, OwnedMediaMetadata, PlatformConfig,
+    PlaylistOrdering,
+};
+
+/// A handle to OS media controls, for async applications. (Only available on MPRIS)
+///
+/// Unlike [`MediaControls`](crate::MediaControls), no service thread is spawned: the
+/// MPRIS service is a future which the application spawns on its own runtime, and the
+/// errors of the setters are returned to the caller. Requires the `async` feature.
+///
+/// The controls don't reconnect if the connection to the bus is lost: the service future
+/// completes, the setters return the error, and the controls have to be attached again.
+#[derive(Debug)]
+pub struct AsyncMediaControls {
+    service: Option<Service>,
+    state: Arc<Mutex<ServiceState>>,
+    dbus_connection: DbusConnection,
+    bus_name: String,
+    friendly_name: String,
+    capabilities: Capabilities,
+    root_properties: RootProperties,
+    events: (
+        async_channel::Sender<MediaControlEvent>,
+        async_channel::Receiver<MediaControlEvent>,
+    ),
+}
+
+#[derive(Debug)]
+struct Service {
+    connection: Connection,
+    /// Stops the future returned by `attach`. It is sent a message once the controls are
+    /// detached. If they are dropped instead, it is closed, and the future removes the
+    /// controls from the bus before completing.
+    stop: async_channel::Sender<()>,
+}
+
+impl AsyncMediaControls {
+    /// Create media controls with the specified config.
+    pub fn new(config: PlatformConfig) -> Result<Self, Error> {
+        let root_properties = RootProperties::new(&config);
+        let PlatformConfig {
+            dbus_name,
+            unique_dbus_name,
+            dbus_connection,
+            display_name,
+            capabilities,
+            ..
+        } = config;
+
+        Ok(Self {
+            service: None,
+            state: Arc::new(Mutex::new(ServiceState::new(
+                capabilities,
+                root_properties.clone(),
+            ))),
+            dbus_connection,
+            bus_name: bus_name(dbus_name, unique_dbus_name),
+            friendly_name: display_name.to_string(),
+            capabilities,
+            root_properties,
+            events: async_channel::unbounded(),
+        })
+    }
+
+    /// Publish the media controls on the bus. The events are sent to the
+    /// [`events`](Self::events) stream.
+    ///
+    /// The returned future handles the requests of MPRIS clients, and must be spawned by the
+    /// application on its own runtime. It completes once the controls are detached or dropped,
+    /// or if the connection to the bus is lost.
+    pub async fn attach(&mut self) -> Result<impl Future<Output = ()> + Send + 'static, Error> {
+        self.detach().await?;
+
+        let state = Arc::new(Mutex::new(ServiceState::new(
+            self.capabilities,
+            self.root_properties.clone(),
+        )));
+        let events = self.events.0.clone();
+        let event_handler: BoxedEventHandler = Box::new(move |event| {
+            // The receiver is owned by `self`, so the channel is never closed.
+            events.try_send(event).ok();
+            Ok(())
+        });
+        let (connection, name_changes) = connect(
+            &self.dbus_connection,
+            &self.bus_name,
+            self.friendly_name.clone(),
+            &state,
+            Arc::new(Mutex::new(event_handler)),
+            false,
+        )
+        .await?;
+
+        let (stop, stopped) = async_channel::bounded(1);
+        let service = {
+            let connection = connection.clone();
+            let bus_name = self.bus_name.clone();
+            async move {
+                let serve = async {
+                    // The stop is checked first, so that releasing the name on detach isn't
+                    // reported as a lost name.
+                    let detached = Box::pin(async { stopped.recv().await.is_ok() });
+                    // The controls are removed here if they were dropped, while `detach`
+                    // removes them itself.
+                    if let Either::Left((false, _)) = future::select(detached, name_changes).await {
+                        disconnect(&connection, &bus_name).await.ok();
+                    }
+                };
+                run_with_executor(&connection, serve).await
+            }
+        };
+
+        self.state = state;
+        self.service = Some(Service { connection, stop });
+        Ok(service)
+    }
+
+    /// Remove the media controls from the bus.
+    pub async fn detach(&mut self) -> Result<(), Error> {
+        if let Some(Service { connection, stop }) = self.service.take() {
+            stop.try_send(()).ok();
+            // The future returned by `attach` may not be running anymore, so the replies
+            // are received here.
+            run_with_executor(&connection, disconnect(&connection, &self.bus_name)).await?;
+        }
+        Ok(())
+    }
+
+    /// The events sent by the OS media controls.
+    ///
+    /// Every event is delivered once: if this is called several times, each event goes to
+    /// only one of the streams.
+    ///
+    /// The events are queued, so the method calls which send them always succeed: an
+    /// [`EventError`](crate::EventError) can't be sent back from here.
+    pub fn events(&self) -> impl Stream<Item = MediaControlEvent> + Send + Unpin + 'static {
+        Box::pin(self.events.1.clone())
+    }
+
+    /// Set the current playback status.
+    pub async fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangePlayback(playback))
+            .await
+    }
+
+    /// Set the metadata of the currently playing media item, either as a [`MediaMetadata`] or
+    /// as an [`OwnedMediaMetadata`].
+    pub async fn set_metadata<M>(&mut self, metadata: M) -> Result<(), Error>
+    where
+        M: Into<OwnedMediaMetadata>,
+    {
+        self.send_internal_event(InternalEvent::change_metadata(metadata.into())?)
+            .await
+    }
+
+    /// Change some fields of the metadata, and keep the others.
+    ///
+    /// See [`MediaControls::update_metadata`](crate::MediaControls::update_metadata).
+    pub async fn update_metadata(&mut self, patch: MetadataPatch) -> Result<(), Error> {
+        match patch.applied_to(&self.metadata()) {
+            Some(metadata) => self.set_metadata(metadata).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Set the volume level (0.0 - 1.0)
+    pub async fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeVolume(volume))
+            .await
+    }
+
+    /// Set whether shuffle is enabled.
+    pub async fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeShuffle(shuffle))
+            .await
+    }
+
+    /// Set the loop/repeat status.
+    pub async fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeLoopStatus(loop_status))
+            .await
+    }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    pub async fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate(rate)?)
+            .await
+    }
+
+    /// Set the range of playback rates that clients may request. The range must include
+    /// 1.0. By default only 1.0 is allowed.
+    pub async fn set_rate_range(&mut self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate_range(minimum, maximum)?)
+            .await
+    }
+
+    /// Set whether the media player is in fullscreen.
+    pub async fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeFullscreen(fullscreen))
+            .await
+    }
+
+    /// Notify clients that the position jumped to `position`, e.g. after a seek, and update
+    /// the reported position accordingly.
+    pub async fn notify_seeked(&mut self, position: MediaPosition) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::NotifySeeked(position))
+            .await
+    }
+
+    /// Set the actions supported by the media player.
+    pub async fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.capabilities = capabilities;
+        self.send_internal_event(InternalEvent::ChangeCapabilities(capabilities))
+            .await
+    }
+
+    /// Replace the whole tracklist, optionally specifying the id of the current track.
+    ///
+    /// See [`MediaControls::set_tracklist`](crate::MediaControls::set_tracklist).
+    pub async fn set_tracklist(
+        &mut self,
+        tracks: &[(&str, MediaMetadata<'_>)],
+        current: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_tracklist(tracks, current)?)
+            .await
+    }
+
+    /// Insert a track into the tracklist, right after the track `after`.
+    /// If `after` is `None`, the track is inserted at the beginning.
+    ///
+    /// See [`MediaControls::insert_track`](crate::MediaControls::insert_track).
+    pub async fn insert_track(
+        &mut self,
+        id: &str,
+        metadata: MediaMetadata<'_>,
+        after: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::insert_track(id, metadata, after)?)
+            .await
+    }
+
+    /// Remove a track from the tracklist.
+    pub async fn remove_track(&mut self, id: &str) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::remove_track(id)?)
+            .await
+    }
+
+    /// Set the metadata of a track in the tracklist.
+    pub async fn set_track_metadata(
+        &mut self,
+        id: &str,
+        metadata: MediaMetadata<'_>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_track_metadata(id, metadata)?)
+            .await
+    }
+
+    /// Set the playlists which can be activated by the user.
+    pub async fn set_playlists(&mut self, playlists: &[MediaPlaylist<'_>]) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlists(playlists)?)
+            .await
+    }
+
+    /// Set the id of the currently active playlist, or `None` if there isn't one.
+    pub async fn set_active_playlist(&mut self, id: Option<&str>) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_active_playlist(id))
+            .await
+    }
+
+    /// Set the orderings in which the playlists can be listed.
+    ///
+    /// See [`MediaControls::set_playlist_orderings`](crate::MediaControls::set_playlist_orderings).
+    pub async fn set_playlist_orderings(
+        &mut self,
+        orderings: &[PlaylistOrdering],
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlist_orderings(orderings))
+            .await
+    }
+
+    /// The metadata currently published.
+    ///
+    /// See [`MediaControls::metadata`](crate::MediaControls::metadata).
+    pub fn metadata(&self) -> OwnedMediaMetadata {
+        self.state.lock().unwrap().published_metadata()
+    }
+
+    /// The playback status currently published.
+    pub fn playback(&self) -> MediaPlayback {
+        self.state.lock().unwrap().current_playback()
+    }
+
+    /// The volume level currently published.
+    pub fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
+
+    /// The actions currently published as supported.
+    ///
+    /// See [`MediaControls::capabilities`](crate::MediaControls::capabilities).
+    pub fn capabilities(&self) -> Capabilities {
+        self.state.lock().unwrap().capabilities
+    }
+
+    async fn send_internal_event(&mut self, event: InternalEvent) -> Result<(), Error> {
+        let service = self.service.as_ref().ok_or(Error::NotAttached)?;
+        let notifications = self.state.lock().unwrap().update(event)?;
+        emit(&service.connection, notifications).await?;
+        Ok(())
+    }
+}
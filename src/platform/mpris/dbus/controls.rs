@@ -1,279 +1,623 @@
 use dbus::arg::{RefArg, Variant};
+use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
 use dbus::blocking::Connection;
-use dbus::channel::{MatchingReceiver, Sender};
+use dbus::channel::{BusType, Channel, MatchingReceiver, Sender};
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
-use dbus::message::SignalArgs;
-use dbus::Path;
+use dbus::message::{MatchRule, MessageType, SignalArgs};
+use dbus::{Message, Path};
 use std::collections::HashMap;
-use std::convert::From;
-use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixStream;
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use std::time::Duration;
-
-use super::super::Error;
-use crate::{MediaControlEvent, MediaMetadata, MediaPlayback, PlatformConfig};
-
-/// A handle to OS media controls.
-pub struct MediaControls {
+use std::time::{Duration, Instant};
+
+use super::super::state::{
+    filter_events, notify, InternalEvent, MetadataDict, MprisValue, Notifications,
+    PropertiesChanged, ServiceState, SharedEventHandler, Signal,
+};
+use super::super::{
+    bus_name, Backoff, Error, OwnedPlaylist, RootProperties, OBJECT_PATH, PLAYER_INTERFACE,
+    PLAYLISTS_INTERFACE, TRACKLIST_INTERFACE,
+};
+use crate::controls::panic_message;
+use crate::{
+    BoxedEventHandler, Capabilities, DbusConnection, LoopStatus, MediaControlEvent,
+    MediaControlsBackend, MediaMetadata, MediaPlayback, MediaPlaylist, MediaPosition,
+    OwnedMediaMetadata, PlatformConfig, PlaylistOrdering,
+};
+
+/// The MPRIS backend built on the `dbus` crate, which uses libdbus.
+pub struct DbusBackend {
     thread: Option<ServiceThreadHandle>,
-    dbus_name: String,
+    state: Arc<Mutex<ServiceState>>,
+    dbus_connection: DbusConnection,
+    bus_name: String,
     friendly_name: String,
+    capabilities: Capabilities,
+    root_properties: RootProperties,
 }
 
 struct ServiceThreadHandle {
-    event_channel: mpsc::Sender<InternalEvent>,
+    event_channel: mpsc::Sender<Notifications>,
+    /// Written to after every event, to wake up the service thread.
+    waker: UnixStream,
     thread: JoinHandle<Result<(), Error>>,
+    /// Kept to restart the service thread if it stops.
+    event_handler: SharedEventHandler,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-enum InternalEvent {
-    ChangeMetadata(OwnedMetadata),
-    ChangePlayback(MediaPlayback),
-    ChangeVolume(f64),
-    Kill,
-}
-
-#[derive(Debug)]
-pub struct ServiceState {
-    pub metadata: OwnedMetadata,
-    pub metadata_dict: HashMap<String, Variant<Box<dyn RefArg>>>,
-    pub playback_status: MediaPlayback,
-    pub volume: f64,
-}
-
-impl ServiceState {
-    pub fn set_metadata(&mut self, metadata: OwnedMetadata) {
-        self.metadata_dict = create_metadata_dict(&metadata);
-        self.metadata = metadata;
-    }
-
-    pub fn get_playback_status(&self) -> &'static str {
-        match self.playback_status {
-            MediaPlayback::Playing { .. } => "Playing",
-            MediaPlayback::Paused { .. } => "Paused",
-            MediaPlayback::Stopped => "Stopped",
-        }
-    }
-}
-
-pub fn create_metadata_dict(metadata: &OwnedMetadata) -> HashMap<String, Variant<Box<dyn RefArg>>> {
-    let mut dict = HashMap::<String, Variant<Box<dyn RefArg>>>::new();
-
-    let mut insert = |k: &str, v| dict.insert(k.to_string(), Variant(v));
-
-    let OwnedMetadata {
-        ref title,
-        ref album,
-        ref artist,
-        ref cover_url,
-        ref duration,
-    } = metadata;
-
-    // TODO: this is just a workaround to enable SetPosition.
-    let path = Path::new("/").unwrap();
-
-    // MPRIS
-    insert("mpris:trackid", Box::new(path));
-
-    if let Some(length) = duration {
-        insert("mpris:length", Box::new(*length));
-    }
-    if let Some(cover_url) = cover_url {
-        insert("mpris:artUrl", Box::new(cover_url.clone()));
-    }
-
-    // Xesam
-    if let Some(title) = title {
-        insert("xesam:title", Box::new(title.clone()));
-    }
-    if let Some(artist) = artist {
-        insert("xesam:artist", Box::new(vec![artist.clone()]));
-    }
-    if let Some(album) = album {
-        insert("xesam:album", Box::new(album.clone()));
-    }
-
-    dict
-}
-
-#[derive(Clone, PartialEq, Eq, Debug, Default)]
-pub struct OwnedMetadata {
-    pub title: Option<String>,
-    pub album: Option<String>,
-    pub artist: Option<String>,
-    pub cover_url: Option<String>,
-    pub duration: Option<i64>,
-}
-
-impl From<MediaMetadata<'_>> for OwnedMetadata {
-    fn from(other: MediaMetadata) -> Self {
-        OwnedMetadata {
-            title: other.title.map(|s| s.to_string()),
-            artist: other.artist.map(|s| s.to_string()),
-            album: other.album.map(|s| s.to_string()),
-            cover_url: other.cover_url.map(|s| s.to_string()),
-            // TODO: This should probably not have an unwrap
-            duration: other.duration.map(|d| d.as_micros().try_into().unwrap()),
-        }
-    }
-}
-
-impl MediaControls {
+impl DbusBackend {
     /// Create media controls with the specified config.
+    ///
+    /// Fails with `Error::ZbusConnectionUnsupported` if the config holds a
+    /// `DbusConnection::Zbus`, which only `ZbusBackend` can share.
     pub fn new(config: PlatformConfig) -> Result<Self, Error> {
+        let root_properties = RootProperties::new(&config);
         let PlatformConfig {
             dbus_name,
+            unique_dbus_name,
+            dbus_connection,
             display_name,
+            capabilities,
             ..
         } = config;
 
+        #[cfg(feature = "zbus")]
+        if let DbusConnection::Zbus(_) = dbus_connection {
+            return Err(Error::ZbusConnectionUnsupported);
+        }
+
         Ok(Self {
             thread: None,
-            dbus_name: dbus_name.to_string(),
+            state: Arc::new(Mutex::new(ServiceState::new(
+                capabilities,
+                root_properties.clone(),
+            ))),
+            dbus_connection,
+            bus_name: bus_name(dbus_name, unique_dbus_name),
             friendly_name: display_name.to_string(),
+            capabilities,
+            root_properties,
         })
     }
 
-    /// Attach the media control events to a handler.
-    pub fn attach<F>(&mut self, event_handler: F) -> Result<(), Error>
-    where
-        F: Fn(MediaControlEvent) + Send + 'static,
-    {
-        self.detach()?;
+    fn send_internal_event(&mut self, event: InternalEvent) -> Result<(), Error> {
+        let thread = self.thread.as_mut().ok_or(Error::ThreadNotRunning)?;
+        let notifications = self.state.lock().unwrap().update(event)?;
+        if thread.thread.is_finished() {
+            return self.restart();
+        }
+        if notifications.is_empty() {
+            return Ok(());
+        }
+        if thread.event_channel.send(notifications).is_err() {
+            return self.restart();
+        }
+        wake(&mut thread.waker);
+        Ok(())
+    }
 
-        let dbus_name = self.dbus_name.clone();
+    /// Connects to the bus and spawns the service thread.
+    fn start(
+        &self,
+        state: Arc<Mutex<ServiceState>>,
+        event_handler: SharedEventHandler,
+    ) -> Result<ServiceThreadHandle, Error> {
+        let bus_name = self.bus_name.clone();
         let friendly_name = self.friendly_name.clone();
         let (event_channel, rx) = mpsc::channel();
 
         // Check if the connection can be created BEFORE spawning the new thread
-        let conn = Connection::new_session()?;
-        let name = format!("org.mpris.MediaPlayer2.{}", dbus_name);
-        conn.request_name(name, false, true, false)?;
-
-        self.thread = Some(ServiceThreadHandle {
+        let conn = connect(&self.dbus_connection, &bus_name)?;
+
+        let (waker, wakeup) = UnixStream::pair().map_err(io_error)?;
+        waker.set_nonblocking(true).map_err(io_error)?;
+        wakeup.set_nonblocking(true).map_err(io_error)?;
+
+        let mut service = Service {
+            dbus_connection: self.dbus_connection.clone(),
+            bus_name,
+            friendly_name,
+            state,
+            event_handler: event_handler.clone(),
+            event_channel: rx,
+            wakeup,
+        };
+        Ok(ServiceThreadHandle {
             event_channel,
-            thread: thread::spawn(move || run_service(conn, friendly_name, event_handler, rx)),
-        });
+            waker,
+            thread: thread::spawn(move || service.supervise(conn)),
+            event_handler,
+        })
+    }
+
+    /// Replaces a service thread which stopped, e.g. because the bus name was taken while it
+    /// was reconnecting, by a new one which publishes the same state.
+    fn restart(&mut self) -> Result<(), Error> {
+        let event_handler = match &self.thread {
+            Some(thread) => thread.event_handler.clone(),
+            None => return Err(Error::ThreadNotRunning),
+        };
+        // The old thread is only replaced once the new one is running, so that the next
+        // call tries again if this one fails.
+        let thread = self.start(self.state.clone(), event_handler)?;
+        if let Some(old_thread) = self.thread.replace(thread) {
+            // Why it stopped doesn't matter anymore.
+            old_thread.thread.join().ok();
+        }
+        Ok(())
+    }
+}
+
+impl MediaControlsBackend for DbusBackend {
+    /// Attach the media control events to a handler.
+    ///
+    /// If the connection to the bus is lost, the service thread reconnects and publishes the
+    /// current state again. The handler receives [`MediaControlEvent::Disconnected`] and
+    /// [`MediaControlEvent::Reconnected`] meanwhile. If the service thread stops anyway, it
+    /// is restarted by the next call which updates the controls.
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error> {
+        self.detach()?;
+
+        let state = Arc::new(Mutex::new(ServiceState::new(
+            self.capabilities,
+            self.root_properties.clone(),
+        )));
+        let thread = self.start(state.clone(), Arc::new(Mutex::new(event_handler)))?;
+        self.state = state;
+        self.thread = Some(thread);
         Ok(())
     }
 
     /// Detach the event handler.
-    pub fn detach(&mut self) -> Result<(), Error> {
-        if let Some(ServiceThreadHandle {
-            event_channel,
-            thread,
-        }) = self.thread.take()
-        {
-            // We don't care about the result of this event, since we immedieately
-            // check if the thread has panicked on the next line.
-            event_channel.send(InternalEvent::Kill).ok();
+    fn detach(&mut self) -> Result<(), Error> {
+        if let Some(handle) = self.thread.take() {
+            // The service thread stops once the channel and the socket are closed.
+            drop(handle.event_channel);
+            drop(handle.waker);
             // One error in case the thread panics, and the other one in case the
             // thread has returned an error.
-            thread.join().map_err(|_| Error::ThreadPanicked)??;
+            handle
+                .thread
+                .join()
+                .map_err(|payload| Error::ThreadPanicked(panic_message(&*payload)))??;
         }
         Ok(())
     }
 
     /// Set the current playback status.
-    pub fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
         self.send_internal_event(InternalEvent::ChangePlayback(playback))
     }
 
     /// Set the metadata of the currently playing media item.
-    pub fn set_metadata(&mut self, metadata: MediaMetadata) -> Result<(), Error> {
-        self.send_internal_event(InternalEvent::ChangeMetadata(metadata.into()))
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_metadata(metadata)?)
     }
 
     /// Set the volume level (0.0-1.0) (Only available on MPRIS)
-    pub fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+    fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
         self.send_internal_event(InternalEvent::ChangeVolume(volume))
     }
 
-    fn send_internal_event(&mut self, event: InternalEvent) -> Result<(), Error> {
-        let thread = &self.thread.as_ref().ok_or(Error::ThreadNotRunning)?;
-        thread
-            .event_channel
-            .send(event)
-            .map_err(|_| Error::ThreadPanicked)
+    /// Set whether shuffle is enabled.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeShuffle(shuffle))
+    }
+
+    /// Set the loop/repeat status.
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeLoopStatus(loop_status))
+    }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate(rate)?)
+    }
+
+    /// Set the range of playback rates that clients may request. The range must include
+    /// 1.0. By default only 1.0 is allowed. (Only available on MPRIS)
+    fn set_rate_range(&mut self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate_range(minimum, maximum)?)
+    }
+
+    /// Set whether the media player is in fullscreen. (Only available on MPRIS)
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeFullscreen(fullscreen))
+    }
+
+    /// Notify clients that the position jumped to `position`, e.g. after a seek, and update
+    /// the reported position accordingly. (Only available on MPRIS)
+    fn notify_seeked(&mut self, position: MediaPosition) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::NotifySeeked(position))
+    }
+
+    /// Set the actions supported by the media player.
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.capabilities = capabilities;
+        self.send_internal_event(InternalEvent::ChangeCapabilities(capabilities))
+    }
+
+    /// Replace the whole tracklist, optionally specifying the id of the current track.
+    /// (Only available on MPRIS)
+    fn set_tracklist(
+        &mut self,
+        tracks: &[(&str, MediaMetadata)],
+        current: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_tracklist(tracks, current)?)
+    }
+
+    /// Insert a track into the tracklist, right after the track `after`.
+    /// If `after` is `None`, the track is inserted at the beginning. (Only available on MPRIS)
+    fn insert_track(
+        &mut self,
+        id: &str,
+        metadata: MediaMetadata,
+        after: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::insert_track(id, metadata, after)?)
+    }
+
+    /// Remove a track from the tracklist. (Only available on MPRIS)
+    fn remove_track(&mut self, id: &str) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::remove_track(id)?)
+    }
+
+    /// Set the metadata of a track in the tracklist. (Only available on MPRIS)
+    fn set_track_metadata(&mut self, id: &str, metadata: MediaMetadata) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_track_metadata(id, metadata)?)
+    }
+
+    /// Set the playlists which can be activated by the user. (Only available on MPRIS)
+    fn set_playlists(&mut self, playlists: &[MediaPlaylist]) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlists(playlists)?)
+    }
+
+    /// Set the id of the currently active playlist, or `None` if there isn't one.
+    /// (Only available on MPRIS)
+    fn set_active_playlist(&mut self, id: Option<&str>) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_active_playlist(id))
+    }
+
+    /// Set the orderings in which the playlists can be listed. (Only available on MPRIS)
+    fn set_playlist_orderings(&mut self, orderings: &[PlaylistOrdering]) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlist_orderings(orderings))
+    }
+
+    fn metadata(&self) -> OwnedMediaMetadata {
+        self.state.lock().unwrap().published_metadata()
+    }
+
+    fn playback(&self) -> MediaPlayback {
+        self.state.lock().unwrap().current_playback()
+    }
+
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        self.state.lock().unwrap().capabilities
     }
 }
 
-fn run_service<F>(
-    conn: Connection,
+/// Opens a connection to the bus, and requests `bus_name`.
+fn connect(dbus_connection: &DbusConnection, bus_name: &str) -> Result<Connection, Error> {
+    let mut channel = match dbus_connection {
+        DbusConnection::Session => Channel::get_private(BusType::Session)?,
+        DbusConnection::System => Channel::get_private(BusType::System)?,
+        DbusConnection::Address(address) => {
+            let mut channel = Channel::open_private(address)?;
+            channel.register()?;
+            channel
+        }
+        #[cfg(feature = "zbus")]
+        DbusConnection::Zbus(_) => return Err(Error::ZbusConnectionUnsupported),
+    };
+    channel.set_watch_enabled(true);
+    let conn = Connection::from(channel);
+    request_name(&conn, bus_name)?;
+    Ok(conn)
+}
+
+/// Requests `name` on the bus, letting another application take it over later.
+///
+/// The name isn't taken from an application which already owns it.
+fn request_name(conn: &Connection, name: &str) -> Result<(), Error> {
+    match conn.request_name(name, true, false, false)? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(()),
+        RequestNameReply::InQueue | RequestNameReply::Exists => {
+            // Don't wait in the queue for the name to be released.
+            conn.release_name(name)?;
+            Err(Error::NameTaken(name.to_owned()))
+        }
+    }
+}
+
+/// What the service thread needs to publish the controls, across reconnections.
+struct Service {
+    dbus_connection: DbusConnection,
+    bus_name: String,
     friendly_name: String,
-    event_handler: F,
-    event_channel: mpsc::Receiver<InternalEvent>,
-) -> Result<(), Error>
-where
-    F: Fn(MediaControlEvent) + Send + 'static,
-{
-    let state = Arc::new(Mutex::new(ServiceState {
-        metadata: Default::default(),
-        metadata_dict: create_metadata_dict(&Default::default()),
-        playback_status: MediaPlayback::Stopped,
-        volume: 1.0,
-    }));
-    let event_handler = Arc::new(Mutex::new(event_handler));
-    let seeked_signal = Arc::new(Mutex::new(None));
-
-    let mut cr =
-        super::interfaces::register_methods(&state, &event_handler, friendly_name, seeked_signal);
-
-    conn.start_receive(
-        dbus::message::MatchRule::new_method_call(),
-        Box::new(move |msg, conn| {
-            cr.handle_message(msg, conn).unwrap();
-            true
-        }),
-    );
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: SharedEventHandler,
+    event_channel: mpsc::Receiver<Notifications>,
+    wakeup: UnixStream,
+}
 
-    loop {
-        if let Ok(event) = event_channel.recv_timeout(Duration::from_millis(10)) {
-            if event == InternalEvent::Kill {
-                break;
+impl Service {
+    /// Serves the controls on `conn`, and reconnects with backoff whenever the connection
+    /// is lost, until the controls are detached.
+    fn supervise(&mut self, mut conn: Connection) -> Result<(), Error> {
+        loop {
+            if self.run(conn).is_ok() {
+                return Ok(());
             }
+            notify(&self.event_handler, MediaControlEvent::Disconnected);
 
-            let mut changed_properties = HashMap::new();
+            let mut backoff = Backoff::new();
+            conn = loop {
+                if !self.wait(backoff.next_delay())? {
+                    return Ok(());
+                }
+                match connect(&self.dbus_connection, &self.bus_name) {
+                    Ok(conn) => break conn,
+                    Err(err @ Error::NameTaken(_)) => return Err(err),
+                    Err(_) => continue,
+                }
+            };
+            notify(&self.event_handler, MediaControlEvent::Reconnected);
+        }
+    }
 
-            match event {
-                InternalEvent::ChangeMetadata(metadata) => {
-                    let mut state = state.lock().unwrap();
-                    state.set_metadata(metadata);
-                    changed_properties.insert(
-                        "Metadata".to_owned(),
-                        Variant(state.metadata_dict.box_clone()),
-                    );
+    /// Serves the controls on `conn`. Returns `Ok` once the controls are detached, and an
+    /// error if the connection is lost.
+    fn run(&mut self, conn: Connection) -> Result<(), Error> {
+        // The name is owned since it was requested, so the first NameAcquired is ignored.
+        let mut owns_name = true;
+        let bus_name = self.bus_name.clone();
+        let name_handler = self.event_handler.clone();
+        conn.start_receive(
+            MatchRule::new()
+                .with_type(MessageType::Signal)
+                .with_sender("org.freedesktop.DBus")
+                .with_interface("org.freedesktop.DBus"),
+            Box::new(move |msg, _| {
+                let owns = match msg.member().as_deref() {
+                    Some("NameLost") => false,
+                    Some("NameAcquired") => true,
+                    _ => return true,
+                };
+                if msg.read1::<&str>().ok() == Some(bus_name.as_str()) && owns != owns_name {
+                    owns_name = owns;
+                    let event = if owns {
+                        MediaControlEvent::NameAcquired
+                    } else {
+                        MediaControlEvent::NameLost
+                    };
+                    notify(&name_handler, event);
                 }
-                InternalEvent::ChangePlayback(playback) => {
-                    let mut state = state.lock().unwrap();
-                    state.playback_status = playback;
-                    changed_properties.insert(
-                        "PlaybackStatus".to_owned(),
-                        Variant(Box::new(state.get_playback_status().to_string())),
-                    );
+                true
+            }),
+        );
+
+        let event_handler = Arc::new(filter_events(&self.state, self.event_handler.clone()));
+        let mut cr = super::interfaces::register_methods(
+            &self.state,
+            &event_handler,
+            self.friendly_name.clone(),
+        );
+        conn.start_receive(
+            dbus::message::MatchRule::new_method_call(),
+            Box::new(move |msg, conn| {
+                cr.handle_message(msg, conn).unwrap();
+                true
+            }),
+        );
+
+        let dbus_fd = conn.channel().watch().fd;
+
+        loop {
+            loop {
+                let Notifications {
+                    signals,
+                    properties_changed,
+                } = match self.event_channel.try_recv() {
+                    Ok(notifications) => notifications,
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+                };
+
+                for signal in signals {
+                    conn.send(signal_message(signal)).ok();
                 }
-                InternalEvent::ChangeVolume(volume) => {
-                    let mut state = state.lock().unwrap();
-                    state.volume = volume;
-                    changed_properties.insert("Volume".to_owned(), Variant(Box::new(volume)));
+                for properties in properties_changed {
+                    conn.send(properties_changed_message(properties)).ok();
                 }
-                _ => (),
             }
 
-            let properties_changed = PropertiesPropertiesChanged {
-                interface_name: "org.mpris.MediaPlayer2.Player".to_owned(),
-                changed_properties,
-                invalidated_properties: Vec::new(),
-            };
+            // Handle every message which has arrived. This also writes out what was sent above,
+            // as far as the socket allows it without blocking.
+            while conn.process(Duration::ZERO)? {}
 
-            conn.send(
-                properties_changed.to_emit_message(&Path::new("/org/mpris/MediaPlayer2").unwrap()),
-            )
-            .ok();
+            // Sleep until there's something to read from the bus or from the app, or until the
+            // rest of the outgoing messages can be written.
+            let mut dbus_events = libc::POLLIN;
+            if conn.channel().has_messages_to_send() {
+                dbus_events |= libc::POLLOUT;
+            }
+            let mut fds = [
+                libc::pollfd {
+                    fd: dbus_fd,
+                    events: dbus_events,
+                    revents: 0,
+                },
+                libc::pollfd {
+                    fd: self.wakeup.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                },
+            ];
+            poll(&mut fds, None)?;
+
+            if fds[1].revents != 0 {
+                self.drain_wakeup();
+            }
         }
-        conn.process(Duration::from_millis(1000))?;
     }
 
-    Ok(())
+    /// Waits for `delay`, dropping the updates sent meanwhile. Returns `false` if the
+    /// controls were detached.
+    fn wait(&mut self, delay: Duration) -> Result<bool, Error> {
+        let deadline = Instant::now() + delay;
+        loop {
+            loop {
+                match self.event_channel.try_recv() {
+                    Ok(_) => {}
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return Ok(false),
+                }
+            }
+
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                return Ok(true);
+            }
+            let mut fds = [libc::pollfd {
+                fd: self.wakeup.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            }];
+            poll(&mut fds, Some(timeout))?;
+            if fds[0].revents != 0 {
+                self.drain_wakeup();
+            }
+        }
+    }
+
+    fn drain_wakeup(&mut self) {
+        // The events themselves are in the channel, so the bytes can be discarded.
+        let mut buf = [0; 64];
+        while matches!(self.wakeup.read(&mut buf), Ok(n) if n > 0) {}
+    }
+}
+
+/// Wakes up the service thread, so that it handles the events sent to it.
+fn wake(waker: &mut UnixStream) {
+    // If the socket is full the service thread has yet to wake up, and if it is closed the
+    // thread has stopped. Neither needs any handling here.
+    waker.write_all(&[0]).ok();
+}
+
+/// Blocks until one of `fds` is ready, or until `timeout` has passed.
+fn poll(fds: &mut [libc::pollfd], timeout: Option<Duration>) -> Result<(), Error> {
+    // Rounded up, so that the timeout doesn't end early.
+    let timeout = timeout.map_or(-1, |timeout| {
+        let millis = (timeout.as_micros() + 999) / 1000;
+        millis.min(libc::c_int::MAX as u128) as libc::c_int
+    });
+    loop {
+        // SAFETY: the pointer and length describe a valid, exclusively borrowed slice.
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, timeout) };
+        if ret >= 0 {
+            return Ok(());
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(io_error(err));
+        }
+    }
+}
+
+fn io_error(err: io::Error) -> Error {
+    dbus::Error::new_failed(&err.to_string()).into()
+}
+
+fn properties_changed_message(properties: PropertiesChanged) -> Message {
+    let PropertiesChanged {
+        interface,
+        changed,
+        invalidated,
+    } = properties;
+
+    let properties_changed = PropertiesPropertiesChanged {
+        interface_name: interface.to_owned(),
+        changed_properties: changed
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), Variant(to_ref_arg(value))))
+            .collect(),
+        invalidated_properties: invalidated.into_iter().map(str::to_owned).collect(),
+    };
+
+    properties_changed.to_emit_message(&Path::from(OBJECT_PATH))
+}
+
+fn signal_message(signal: Signal) -> Message {
+    let message = |interface: &str, member: &str| {
+        Message::signal(&Path::from(OBJECT_PATH), &interface.into(), &member.into())
+    };
+
+    match signal {
+        Signal::Seeked(position) => message(PLAYER_INTERFACE, "Seeked").append1(position),
+        Signal::TrackListReplaced { tracks, current } => {
+            let tracks: Vec<Path> = tracks.into_iter().map(Path::from).collect();
+            message(TRACKLIST_INTERFACE, "TrackListReplaced").append2(tracks, Path::from(current))
+        }
+        Signal::TrackAdded { metadata, after } => message(TRACKLIST_INTERFACE, "TrackAdded")
+            .append2(metadata_dict(metadata), Path::from(after)),
+        Signal::TrackRemoved(id) => {
+            message(TRACKLIST_INTERFACE, "TrackRemoved").append1(Path::from(id))
+        }
+        Signal::TrackMetadataChanged { id, metadata } => {
+            message(TRACKLIST_INTERFACE, "TrackMetadataChanged")
+                .append2(Path::from(id), metadata_dict(metadata))
+        }
+        Signal::PlaylistChanged(playlist) => {
+            message(PLAYLISTS_INTERFACE, "PlaylistChanged").append1(playlist_struct(&playlist))
+        }
+    }
+}
+
+/// Converts a value of the service state to a value which can be sent by `dbus`.
+fn to_ref_arg(value: MprisValue) -> Box<dyn RefArg> {
+    match value {
+        MprisValue::Bool(value) => Box::new(value),
+        MprisValue::Int32(value) => Box::new(value),
+        MprisValue::Int64(value) => Box::new(value),
+        MprisValue::UInt32(value) => Box::new(value),
+        MprisValue::Double(value) => Box::new(value),
+        MprisValue::String(value) => Box::new(value),
+        MprisValue::ObjectPath(path) => Box::new(Path::from(path)),
+        MprisValue::StringList(list) => Box::new(list),
+        MprisValue::ActivePlaylist(playlist) => Box::new(active_playlist_struct(playlist.as_ref())),
+        MprisValue::Metadata(dict) => Box::new(metadata_dict(dict)),
+    }
+}
+
+pub fn metadata_dict(dict: MetadataDict) -> HashMap<String, Variant<Box<dyn RefArg>>> {
+    dict.into_iter()
+        .map(|(key, value)| (key, Variant(to_ref_arg(value))))
+        .collect()
+}
+
+/// Converts a playlist to the `(oss)` struct used by the MPRIS Playlists interface.
+pub fn playlist_struct(playlist: &OwnedPlaylist) -> (Path<'static>, String, String) {
+    (
+        Path::from(playlist.id.clone()),
+        playlist.name.clone(),
+        playlist.icon.clone(),
+    )
+}
+
+/// Converts the active playlist to the `(b(oss))` struct of the `ActivePlaylist` property.
+pub fn active_playlist_struct(
+    playlist: Option<&OwnedPlaylist>,
+) -> (bool, (Path<'static>, String, String)) {
+    match playlist {
+        Some(playlist) => (true, playlist_struct(playlist)),
+        None => (false, (Path::from("/"), String::new(), String::new())),
+    }
 }
@@ -1,61 +1,89 @@
 use std::{
-    convert::{TryFrom, TryInto},
+    collections::HashMap,
     sync::{Arc, Mutex},
-    time::Duration,
 };
 
+use dbus::arg::{RefArg, Variant};
 use dbus::Path;
-use dbus_crossroads::{Crossroads, IfaceBuilder};
+use dbus_crossroads::{Crossroads, IfaceBuilder, MethodErr};
 
-use crate::{MediaControlEvent, MediaPlayback, MediaPosition, SeekDirection};
+use crate::{Capabilities, EventError, MediaControlEvent, PlaylistId, TrackId};
 
-use super::controls::{create_metadata_dict, ServiceState};
+use super::super::state::{add_track_event, create_metadata_dict, seek_event, ServiceState};
+use super::super::{
+    get_playlists, loop_status_name, method_error_name, parse_loop_status, OBJECT_PATH,
+    PLAYER_INTERFACE, PLAYLISTS_INTERFACE, ROOT_INTERFACE, TRACKLIST_INTERFACE,
+};
+use super::controls::{active_playlist_struct, metadata_dict, playlist_struct};
 
-// TODO: This type is super messed up, but it's the only way to get seeking working properly
-// on graphical media controls using dbus-crossroads.
-pub type SeekedSignal =
-    Arc<Mutex<Option<Box<dyn Fn(&Path<'_>, &(String,)) -> dbus::Message + Send + Sync>>>>;
+type MetadataDict = HashMap<String, Variant<Box<dyn RefArg>>>;
 
 pub fn register_methods<F>(
     state: &Arc<Mutex<ServiceState>>,
-    event_handler: &Arc<Mutex<F>>,
+    event_handler: &Arc<F>,
     friendly_name: String,
-    seeked_signal: SeekedSignal,
 ) -> Crossroads
 where
-    F: Fn(MediaControlEvent) + Send + 'static,
+    F: Fn(MediaControlEvent) -> Result<(), EventError> + Send + Sync + 'static,
 {
     let mut cr = Crossroads::new();
-    let app_interface = cr.register("org.mpris.MediaPlayer2", {
+    let app_interface = cr.register(ROOT_INTERFACE, {
+        let state = state.clone();
         let event_handler = event_handler.clone();
 
         move |b| {
             b.property("Identity")
                 .get(move |_, _| Ok(friendly_name.clone()));
+            b.property("DesktopEntry").get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().root_properties.desktop_entry.clone())
+            });
 
             register_method(b, &event_handler, "Raise", MediaControlEvent::Raise);
             register_method(b, &event_handler, "Quit", MediaControlEvent::Quit);
 
-            // TODO: allow user to set these properties
-            b.property("CanQuit")
-                .get(|_, _| Ok(true))
-                .emits_changed_true();
-            b.property("CanRaise")
-                .get(|_, _| Ok(true))
+            register_capability(b, &state, "CanQuit", |c| c.can_quit);
+            register_capability(b, &state, "CanRaise", |c| c.can_raise);
+            register_capability(b, &state, "CanSetFullscreen", |c| c.can_set_fullscreen);
+            b.property("Fullscreen")
+                .get({
+                    let state = state.clone();
+                    move |_, _| Ok(state.lock().unwrap().fullscreen)
+                })
+                .set({
+                    let event_handler = event_handler.clone();
+                    move |_, _, fullscreen: bool| {
+                        let event = MediaControlEvent::SetFullscreen(fullscreen);
+                        handle_method(&event_handler, event)?;
+                        Ok(None)
+                    }
+                })
                 .emits_changed_true();
             b.property("HasTracklist")
-                .get(|_, _| Ok(false))
+                .get({
+                    let state = state.clone();
+                    move |_, _| Ok(state.lock().unwrap().tracklist.is_some())
+                })
                 .emits_changed_true();
             b.property("SupportedUriSchemes")
-                .get(move |_, _| Ok(&[] as &[String]))
+                .get({
+                    let state = state.clone();
+                    move |_, _| {
+                        let state = state.lock().unwrap();
+                        Ok(state.root_properties.supported_uri_schemes.clone())
+                    }
+                })
                 .emits_changed_true();
             b.property("SupportedMimeTypes")
-                .get(move |_, _| Ok(&[] as &[String]))
+                .get(move |_, _| {
+                    let state = state.lock().unwrap();
+                    Ok(state.root_properties.supported_mime_types.clone())
+                })
                 .emits_changed_true();
         }
     });
 
-    let player_interface = cr.register("org.mpris.MediaPlayer2.Player", |b| {
+    let player_interface = cr.register(PLAYER_INTERFACE, |b| {
         register_method(b, event_handler, "Next", MediaControlEvent::Next);
         register_method(b, event_handler, "Previous", MediaControlEvent::Previous);
         register_method(b, event_handler, "Pause", MediaControlEvent::Pause);
@@ -66,52 +94,22 @@ where
         b.method("Seek", ("Offset",), (), {
             let event_handler = event_handler.clone();
 
-            move |ctx, _, (offset,): (i64,)| {
-                let abs_offset = offset.unsigned_abs();
-                let direction = if offset > 0 {
-                    SeekDirection::Forward
-                } else {
-                    SeekDirection::Backward
-                };
-
-                (event_handler.lock().unwrap())(MediaControlEvent::SeekBy(
-                    direction,
-                    Duration::from_micros(abs_offset),
-                ));
-                ctx.push_msg(ctx.make_signal("Seeked", ()));
-                Ok(())
-            }
+            move |_, _, (offset,): (i64,)| handle_method(&event_handler, seek_event(offset))
         });
 
         b.method("SetPosition", ("TrackId", "Position"), (), {
             let state = state.clone();
             let event_handler = event_handler.clone();
 
-            move |_, _, (_trackid, position): (Path, i64)| {
-                let state = state.lock().unwrap();
-
-                // According to the MPRIS specification:
-
-                // TODO: If the TrackId argument is not the same as the current
-                // trackid, the call is ignored as stale.
-                // (Maybe it should be optional?)
-
-                if let Some(duration) = state.metadata.duration {
-                    // If the Position argument is greater than the track length, do nothing.
-                    if position > duration {
-                        return Ok(());
-                    }
-                }
-
-                // If the Position argument is less than 0, do nothing.
-                if let Ok(position) = u64::try_from(position) {
-                    let position = Duration::from_micros(position);
-
-                    (event_handler.lock().unwrap())(MediaControlEvent::SetPosition(MediaPosition(
-                        position,
-                    )));
+            move |_, _, (track_id, position): (Path, i64)| {
+                let event = state
+                    .lock()
+                    .unwrap()
+                    .set_position_event(&track_id, position);
+                match event {
+                    Some(event) => handle_method(&event_handler, event),
+                    None => Ok(()),
                 }
-                Ok(())
             }
         });
 
@@ -119,29 +117,48 @@ where
             let event_handler = event_handler.clone();
 
             move |_, _, (uri,): (String,)| {
-                (event_handler.lock().unwrap())(MediaControlEvent::OpenUri(uri));
-                Ok(())
+                handle_method(&event_handler, MediaControlEvent::OpenUri(uri))
             }
         });
 
-        *seeked_signal.lock().unwrap() = Some(b.signal::<(String,), _>("Seeked", ("x",)).msg_fn());
+        // Emitted by the service loop when the application notifies a seek.
+        b.signal::<(i64,), _>("Seeked", ("Position",));
 
         b.property("PlaybackStatus")
             .get({
                 let state = state.clone();
                 move |_, _| {
                     let state = state.lock().unwrap();
-                    Ok(state.get_playback_status().to_string())
+                    Ok(state.playback_status_name().to_string())
                 }
             })
             .emits_changed_true();
 
-        b.property("Rate").get(|_, _| Ok(1.0)).emits_changed_true();
+        b.property("Rate")
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().rate)
+            })
+            .set({
+                let state = state.clone();
+                let event_handler = event_handler.clone();
+                move |_, _, rate: f64| {
+                    let event = state.lock().unwrap().rate_event(rate);
+                    if let Some(event) = event {
+                        handle_method(&event_handler, event)?;
+                    }
+                    Ok(None)
+                }
+            })
+            .emits_changed_true();
 
         b.property("Metadata")
             .get({
                 let state = state.clone();
-                move |_, _| Ok(create_metadata_dict(&state.lock().unwrap().metadata))
+                move |_, _| {
+                    let state = state.lock().unwrap();
+                    Ok(metadata_dict(state.metadata_dict.clone()))
+                }
             })
             .emits_changed_true();
 
@@ -156,81 +173,257 @@ where
             .set({
                 let event_handler = event_handler.clone();
                 move |_, _, volume: f64| {
-                    (event_handler.lock().unwrap())(MediaControlEvent::SetVolume(volume));
-                    Ok(Some(volume))
+                    handle_method(&event_handler, MediaControlEvent::SetVolume(volume))?;
+                    Ok(None)
+                }
+            })
+            .emits_changed_true();
+
+        b.property("Shuffle")
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().shuffle)
+            })
+            .set({
+                let event_handler = event_handler.clone();
+                move |_, _, shuffle: bool| {
+                    handle_method(&event_handler, MediaControlEvent::SetShuffle(shuffle))?;
+                    Ok(None)
+                }
+            })
+            .emits_changed_true();
+
+        b.property("LoopStatus")
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(loop_status_name(state.lock().unwrap().loop_status).to_string())
+            })
+            .set({
+                let event_handler = event_handler.clone();
+                move |_, _, loop_status: String| {
+                    let loop_status = parse_loop_status(&loop_status)
+                        .ok_or_else(|| MethodErr::invalid_arg(&loop_status))?;
+                    handle_method(
+                        &event_handler,
+                        MediaControlEvent::SetLoopStatus(loop_status),
+                    )?;
+                    Ok(None)
                 }
             })
             .emits_changed_true();
 
         b.property("Position").get({
             let state = state.clone();
-            move |_, _| {
-                let state = state.lock().unwrap();
-                let progress: i64 = match state.playback_status {
-                    MediaPlayback::Playing {
-                        progress: Some(progress),
-                    }
-                    | MediaPlayback::Paused {
-                        progress: Some(progress),
-                    } => progress.0.as_micros(),
-                    _ => 0,
-                }
-                .try_into()
-                .unwrap();
-                Ok(progress)
-            }
+            move |_, _| Ok(state.lock().unwrap().position())
         });
 
         b.property("MinimumRate")
-            .get(|_, _| Ok(1.0))
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().minimum_rate)
+            })
             .emits_changed_true();
         b.property("MaximumRate")
-            .get(|_, _| Ok(1.0))
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().maximum_rate)
+            })
             .emits_changed_true();
 
-        b.property("CanGoNext")
-            .get(|_, _| Ok(true))
-            .emits_changed_true();
-        b.property("CanGoPrevious")
-            .get(|_, _| Ok(true))
-            .emits_changed_true();
-        b.property("CanPlay")
-            .get(|_, _| Ok(true))
-            .emits_changed_true();
-        b.property("CanPause")
-            .get(|_, _| Ok(true))
+        register_capability(b, state, "CanGoNext", |c| c.can_go_next);
+        register_capability(b, state, "CanGoPrevious", |c| c.can_go_previous);
+        register_capability(b, state, "CanPlay", |c| c.can_play);
+        register_capability(b, state, "CanPause", |c| c.can_pause);
+        register_capability(b, state, "CanSeek", |c| c.can_seek);
+        register_capability(b, state, "CanControl", |c| c.can_control);
+    });
+
+    let tracklist_interface = cr.register(TRACKLIST_INTERFACE, |b| {
+        b.method("GetTracksMetadata", ("TrackIds",), ("Metadata",), {
+            let state = state.clone();
+
+            move |_, _, (track_ids,): (Vec<Path<'static>>,)| {
+                let state = state.lock().unwrap();
+                let metadata: Vec<MetadataDict> = track_ids
+                    .iter()
+                    .filter_map(|id| {
+                        let metadata = state.get_track(id)?;
+                        Some(metadata_dict(create_metadata_dict(id, metadata)))
+                    })
+                    .collect();
+                Ok((metadata,))
+            }
+        });
+
+        b.method("AddTrack", ("Uri", "AfterTrack", "SetAsCurrent"), (), {
+            let event_handler = event_handler.clone();
+
+            move |_, _, (uri, after_track, set_as_current): (String, Path<'static>, bool)| {
+                handle_method(
+                    &event_handler,
+                    add_track_event(uri, &after_track, set_as_current),
+                )
+            }
+        });
+
+        b.method("RemoveTrack", ("TrackId",), (), {
+            let event_handler = event_handler.clone();
+
+            move |_, _, (track_id,): (Path<'static>,)| {
+                handle_method(
+                    &event_handler,
+                    MediaControlEvent::RemoveTrack(TrackId(track_id.to_string())),
+                )
+            }
+        });
+
+        b.method("GoTo", ("TrackId",), (), {
+            let event_handler = event_handler.clone();
+
+            move |_, _, (track_id,): (Path<'static>,)| {
+                handle_method(
+                    &event_handler,
+                    MediaControlEvent::GoTo(TrackId(track_id.to_string())),
+                )
+            }
+        });
+
+        b.signal::<(Vec<Path<'static>>, Path<'static>), _>(
+            "TrackListReplaced",
+            ("Tracks", "CurrentTrack"),
+        );
+        b.signal::<(MetadataDict, Path<'static>), _>("TrackAdded", ("Metadata", "AfterTrack"));
+        b.signal::<(Path<'static>,), _>("TrackRemoved", ("TrackId",));
+        b.signal::<(Path<'static>, MetadataDict), _>(
+            "TrackMetadataChanged",
+            ("TrackId", "Metadata"),
+        );
+
+        b.property("Tracks")
+            .get({
+                let state = state.clone();
+                move |_, _| {
+                    let tracks: Vec<Path<'static>> = state
+                        .lock()
+                        .unwrap()
+                        .track_ids()
+                        .into_iter()
+                        .map(Path::from)
+                        .collect();
+                    Ok(tracks)
+                }
+            })
+            .emits_changed_invalidates();
+
+        register_capability(b, state, "CanEditTracks", |c| c.can_edit_tracks);
+    });
+
+    let playlists_interface = cr.register(PLAYLISTS_INTERFACE, |b| {
+        b.method("ActivatePlaylist", ("PlaylistId",), (), {
+            let event_handler = event_handler.clone();
+
+            move |_, _, (playlist_id,): (Path<'static>,)| {
+                handle_method(
+                    &event_handler,
+                    MediaControlEvent::ActivatePlaylist(PlaylistId(playlist_id.to_string())),
+                )
+            }
+        });
+
+        b.method(
+            "GetPlaylists",
+            ("Index", "MaxCount", "Order", "ReverseOrder"),
+            ("Playlists",),
+            {
+                let state = state.clone();
+
+                move |_, _, (index, max_count, order, reverse_order): (u32, u32, String, bool)| {
+                    let state = state.lock().unwrap();
+                    let playlists: Vec<(Path<'static>, String, String)> =
+                        get_playlists(&state.playlists, index, max_count, &order, reverse_order)
+                            .into_iter()
+                            .map(playlist_struct)
+                            .collect();
+                    Ok((playlists,))
+                }
+            },
+        );
+
+        b.signal::<((Path<'static>, String, String),), _>("PlaylistChanged", ("Playlist",));
+
+        b.property("PlaylistCount")
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().playlists.len() as u32)
+            })
             .emits_changed_true();
-        b.property("CanSeek")
-            .get(|_, _| Ok(true))
+
+        b.property("Orderings")
+            .get({
+                let state = state.clone();
+                move |_, _| Ok(state.lock().unwrap().playlist_ordering_names())
+            })
             .emits_changed_true();
-        b.property("CanControl")
-            .get(|_, _| Ok(true))
+
+        b.property("ActivePlaylist")
+            .get({
+                let state = state.clone();
+                move |_, _| {
+                    let state = state.lock().unwrap();
+                    Ok(active_playlist_struct(state.get_active_playlist()))
+                }
+            })
             .emits_changed_true();
     });
 
     cr.insert(
-        "/org/mpris/MediaPlayer2",
-        &[app_interface, player_interface],
+        OBJECT_PATH,
+        &[
+            app_interface,
+            player_interface,
+            tracklist_interface,
+            playlists_interface,
+        ],
         (),
     );
 
-    seeked_signal.lock().ok();
-
     cr
 }
 
 fn register_method<F>(
     b: &mut IfaceBuilder<()>,
-    event_handler: &Arc<Mutex<F>>,
+    event_handler: &Arc<F>,
     name: &'static str,
     event: MediaControlEvent,
 ) where
-    F: Fn(MediaControlEvent) + Send + 'static,
+    F: Fn(MediaControlEvent) -> Result<(), EventError> + Send + Sync + 'static,
 {
     let event_handler = event_handler.clone();
 
     b.method(name, (), (), move |_, _, _: ()| {
-        (event_handler.lock().unwrap())(event.clone());
-        Ok(())
+        handle_method(&event_handler, event.clone())
     });
 }
+
+/// Send the event of a method call or of a property change to the handler, and reply with
+/// its error if it fails.
+fn handle_method<F>(event_handler: &Arc<F>, event: MediaControlEvent) -> Result<(), MethodErr>
+where
+    F: Fn(MediaControlEvent) -> Result<(), EventError>,
+{
+    event_handler(event)
+        .map_err(|error| MethodErr::from((method_error_name(&error), error.to_string())))
+}
+
+fn register_capability(
+    b: &mut IfaceBuilder<()>,
+    state: &Arc<Mutex<ServiceState>>,
+    name: &'static str,
+    get: fn(&Capabilities) -> bool,
+) {
+    let state = state.clone();
+
+    b.property(name)
+        .get(move |_, _| Ok(get(&state.lock().unwrap().capabilities)))
+        .emits_changed_true();
+}
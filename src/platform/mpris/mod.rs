@@ -3,38 +3,487 @@
 #[cfg(not(any(feature = "dbus", feature = "zbus")))]
 compile_error!("either feature \"dbus\" or feature \"zbus\" are required");
 
-#[cfg(all(feature = "dbus", feature = "zbus"))]
-compile_error!("feature \"dbus\" and feature \"zbus\" are mutually exclusive");
-
 #[cfg(feature = "zbus")]
 mod zbus;
 #[cfg(feature = "zbus")]
-pub use self::zbus::*;
+pub use self::zbus::ZbusBackend;
 #[cfg(feature = "zbus")]
 extern crate zbus as zbus_crate;
 
 #[cfg(feature = "dbus")]
 mod dbus;
 #[cfg(feature = "dbus")]
-pub use self::dbus::*;
+pub use self::dbus::DbusBackend;
 #[cfg(feature = "dbus")]
 extern crate dbus as dbus_crate;
 
+/// The backend used by [`MediaControls::new`](crate::MediaControls::new). The D-Bus backend
+/// is preferred when both are enabled.
+#[cfg(feature = "dbus")]
+pub(crate) type DefaultBackend = DbusBackend;
+#[cfg(not(feature = "dbus"))]
+pub(crate) type DefaultBackend = ZbusBackend;
+
+mod state;
+
+#[cfg(feature = "async")]
+mod async_controls;
+#[cfg(feature = "async")]
+pub use self::async_controls::AsyncMediaControls;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::{
+    Capabilities, EventError, LoopStatus, MediaControlEvent, MediaPlaylist, PlatformConfig,
+    PlaylistOrdering,
+};
+
 /// A platform-specific error.
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("internal D-Bus error: {0}")]
     #[cfg(feature = "dbus")]
     DbusError(#[from] dbus_crate::Error),
-    #[error("internal D-Bus error: {0}")]
+    #[error("internal zbus error: {0}")]
     #[cfg(feature = "zbus")]
-    DbusError(#[from] zbus_crate::Error),
+    ZbusError(#[from] zbus_crate::Error),
     #[error("D-bus service thread not running. Run MediaControls::attach()")]
     ThreadNotRunning,
-    // NOTE: For now this error is not very descriptive. For now we can't do much about it
-    // since the panic message returned by JoinHandle::join does not implement Debug/Display,
-    // thus we cannot print it, though perhaps there is another way. I will leave this error here,
-    // to at least be able to catch it, but it is preferable to have this thread *not panic* at all.
-    #[error("D-Bus service thread panicked")]
-    ThreadPanicked,
+    // Panics of the event handler are caught and don't stop the service, so this shouldn't
+    // happen.
+    #[error("D-Bus service thread panicked: {0}")]
+    ThreadPanicked(String),
+    #[error("MPRIS service stopped: its future was dropped or returned an error")]
+    #[cfg(feature = "zbus")]
+    ServiceStopped,
+    #[error("media controls not attached. Run AsyncMediaControls::attach()")]
+    #[cfg(feature = "async")]
+    NotAttached,
+    #[error("D-Bus name {0} is already taken by another application")]
+    NameTaken(String),
+    #[error("the D-Bus backend can't share a zbus connection, use ZbusBackend instead")]
+    #[cfg(all(feature = "dbus", feature = "zbus"))]
+    ZbusConnectionUnsupported,
+    #[error("the MPRIS interfaces are already served on the shared zbus connection")]
+    #[cfg(feature = "zbus")]
+    AlreadyServed,
+    #[error("invalid track id {0:?}: must be a D-Bus object path outside of /org/mpris")]
+    InvalidTrackId(String),
+    #[error("track id {0:?} is already in the tracklist")]
+    DuplicateTrackId(String),
+    #[error("track id {0:?} isn't in the tracklist")]
+    UnknownTrackId(String),
+    #[error("invalid playlist id {0:?}: must be a D-Bus object path")]
+    InvalidPlaylistId(String),
+    #[error("invalid metadata key {0:?}: must be a namespace:key outside of mpris and xesam")]
+    InvalidMetadataKey(String),
+    #[error("invalid playback rate {0}: must be a positive number")]
+    InvalidRate(f64),
+    #[error("invalid playback rate range {0}-{1}: must be positive and include 1.0")]
+    InvalidRateRange(f64, f64),
+}
+
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const TRACKLIST_INTERFACE: &str = "org.mpris.MediaPlayer2.TrackList";
+const PLAYLISTS_INTERFACE: &str = "org.mpris.MediaPlayer2.Playlists";
+
+/// The object path at which the MPRIS interfaces are served.
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// The object path used by MPRIS to refer to the absence of a track.
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+fn is_object_path(path: &str) -> bool {
+    path == "/"
+        || (path.starts_with('/')
+            && path[1..].split('/').all(|element| {
+                !element.is_empty()
+                    && element
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }))
+}
+
+/// Checks that a track id is a valid D-Bus object path, which is not inside the
+/// `/org/mpris` namespace, as it is reserved by the MPRIS specification.
+fn validate_track_id(id: &str) -> Result<(), Error> {
+    let is_reserved = id == "/org/mpris" || id.starts_with("/org/mpris/");
+
+    if is_object_path(id) && !is_reserved {
+        Ok(())
+    } else {
+        Err(Error::InvalidTrackId(id.to_owned()))
+    }
+}
+
+/// The well-known name under which the MPRIS service is published.
+fn bus_name(dbus_name: &str, unique: bool) -> String {
+    if unique {
+        format!(
+            "org.mpris.MediaPlayer2.{}.instance{}",
+            dbus_name,
+            std::process::id()
+        )
+    } else {
+        format!("org.mpris.MediaPlayer2.{}", dbus_name)
+    }
+}
+
+/// The properties of the root interface which describe the application, as given in the
+/// config.
+#[derive(Clone, Debug, Default)]
+struct RootProperties {
+    desktop_entry: String,
+    supported_uri_schemes: Vec<String>,
+    supported_mime_types: Vec<String>,
+}
+
+impl RootProperties {
+    fn new(config: &PlatformConfig) -> Self {
+        let to_strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+
+        Self {
+            desktop_entry: config.desktop_entry.to_owned(),
+            supported_uri_schemes: to_strings(config.supported_uri_schemes),
+            supported_mime_types: to_strings(config.supported_mime_types),
+        }
+    }
+
+    /// Checks whether a URI can be opened: its scheme must be one of the supported schemes,
+    /// if any are given, and the MIME type of a local file one of the supported MIME types,
+    /// if any are given. Schemes are compared case-insensitively, as required by RFC 3986.
+    fn supports_uri(&self, uri: &str) -> bool {
+        self.supports_scheme(uri) && self.supports_mime_type(uri)
+    }
+
+    fn supports_scheme(&self, uri: &str) -> bool {
+        if self.supported_uri_schemes.is_empty() {
+            return true;
+        }
+        let scheme = match uri.split_once(':') {
+            Some((scheme, _)) => scheme,
+            None => return false,
+        };
+
+        self.supported_uri_schemes
+            .iter()
+            .any(|supported| supported.eq_ignore_ascii_case(scheme))
+    }
+
+    /// Only the MIME type of local files is known, from their extension. The other URIs, and
+    /// the files whose extension isn't known, are left to the event handler.
+    fn supports_mime_type(&self, uri: &str) -> bool {
+        let mime_types = file_mime_types(uri);
+        if self.supported_mime_types.is_empty() || mime_types.is_empty() {
+            return true;
+        }
+
+        self.supported_mime_types.iter().any(|supported| {
+            mime_types
+                .iter()
+                .any(|mime_type| supported.eq_ignore_ascii_case(mime_type))
+        })
+    }
+}
+
+/// The MIME types of a `file://` URI, guessed from the extension of the file. A type can go by
+/// several names, e.g. `audio/flac` and `audio/x-flac`.
+fn file_mime_types(uri: &str) -> &'static [&'static str] {
+    let path = match uri.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("file://") => &uri[7..],
+        _ => return &[],
+    };
+    let path = path.split(['?', '#']).next().unwrap_or_default();
+    let name = path.rsplit('/').next().unwrap_or_default();
+    let extension = match name.rsplit_once('.') {
+        Some((_, extension)) => extension.to_ascii_lowercase(),
+        None => return &[],
+    };
+
+    match extension.as_str() {
+        "mp3" => &["audio/mpeg", "audio/mp3"],
+        "ogg" | "oga" => &["audio/ogg", "audio/vorbis", "application/ogg"],
+        "opus" => &["audio/opus", "audio/ogg"],
+        "flac" => &["audio/flac", "audio/x-flac"],
+        "wav" => &["audio/wav", "audio/x-wav", "audio/vnd.wave"],
+        "aiff" | "aif" => &["audio/aiff", "audio/x-aiff"],
+        "m4a" => &["audio/mp4", "audio/x-m4a"],
+        "aac" => &["audio/aac", "audio/x-aac"],
+        "wma" => &["audio/x-ms-wma"],
+        "mka" => &["audio/x-matroska"],
+        "mid" | "midi" => &["audio/midi", "audio/x-midi"],
+        "m3u" | "m3u8" => &["audio/x-mpegurl", "application/vnd.apple.mpegurl"],
+        "pls" => &["audio/x-scpls"],
+        "mp4" | "m4v" => &["video/mp4"],
+        "mkv" => &["video/x-matroska"],
+        "webm" => &["video/webm", "audio/webm"],
+        "ogv" => &["video/ogg"],
+        "avi" => &["video/x-msvideo"],
+        "mov" => &["video/quicktime"],
+        _ => &[],
+    }
+}
+
+/// The delays between the attempts to reconnect to the bus: doubled after each failed
+/// attempt, from 100ms up to 10s.
+///
+/// The updates sent meanwhile are dropped, since the new connection serves the state as it
+/// is then. Once another application has taken the name, retrying is pointless.
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    const MIN_DELAY: Duration = Duration::from_millis(100);
+    const MAX_DELAY: Duration = Duration::from_secs(10);
+
+    fn new() -> Self {
+        Self {
+            delay: Self::MIN_DELAY,
+        }
+    }
+
+    fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(Self::MAX_DELAY);
+        delay
+    }
+}
+
+/// Generates a new unique track id, for metadata which doesn't have one.
+fn generate_track_id() -> String {
+    static TRACK_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let number = TRACK_COUNTER.fetch_add(1, Ordering::Relaxed);
+    format!("/souvlaki/track/{number}")
+}
+
+fn validate_playlist_id(id: &str) -> Result<(), Error> {
+    if is_object_path(id) {
+        Ok(())
+    } else {
+        Err(Error::InvalidPlaylistId(id.to_owned()))
+    }
+}
+
+/// The D-Bus error sent back when the event handler fails to carry out a method call.
+pub(super) fn method_error_name(error: &EventError) -> &'static str {
+    match error {
+        EventError::NotSupported => "org.freedesktop.DBus.Error.NotSupported",
+        EventError::NoSuchContent => "org.mpris.MediaPlayer2.Error.NoSuchContent",
+        EventError::Failed(_) => "org.mpris.MediaPlayer2.Error.Failed",
+    }
+}
+
+/// Checks that the keys of custom metadata entries follow the `namespace:key` convention,
+/// outside of the `mpris` and `xesam` namespaces. Their entries are set from the other
+/// fields of the metadata, and must not be overridden.
+fn validate_custom_metadata<K: AsRef<str>, V>(custom: &[(K, V)]) -> Result<(), Error> {
+    let is_name = |name: &str| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+    };
+
+    for (key, _) in custom {
+        let key = key.as_ref();
+        match key.split_once(':') {
+            Some((namespace, name))
+                if is_name(namespace)
+                    && is_name(name)
+                    && namespace != "mpris"
+                    && namespace != "xesam" => {}
+            _ => return Err(Error::InvalidMetadataKey(key.to_string())),
+        }
+    }
+    Ok(())
+}
+
+fn validate_rate(rate: f64) -> Result<(), Error> {
+    if rate.is_finite() && rate > 0.0 {
+        Ok(())
+    } else {
+        Err(Error::InvalidRate(rate))
+    }
+}
+
+/// Checks the range of rates accepted from clients. The MPRIS specification requires
+/// `MinimumRate` to be at most 1.0 and `MaximumRate` to be at least 1.0.
+fn validate_rate_range(minimum: f64, maximum: f64) -> Result<(), Error> {
+    let valid = minimum > 0.0 && minimum <= 1.0 && maximum >= 1.0 && maximum.is_finite();
+
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InvalidRateRange(minimum, maximum))
+    }
+}
+
+/// Converts a rate requested by a client into an event. As required by the MPRIS
+/// specification, a rate of 0.0 acts as a pause and rates outside of the allowed range
+/// are ignored.
+fn rate_event(rate: f64, minimum: f64, maximum: f64) -> Option<MediaControlEvent> {
+    if rate == 0.0 {
+        Some(MediaControlEvent::Pause)
+    } else if rate >= minimum && rate <= maximum {
+        Some(MediaControlEvent::SetRate(rate))
+    } else {
+        None
+    }
+}
+
+/// Applies the MPRIS rule that no playback capability is available when `CanControl`
+/// is false.
+fn effective_capabilities(capabilities: Capabilities) -> Capabilities {
+    let can_control = capabilities.can_control;
+
+    Capabilities {
+        can_go_next: capabilities.can_go_next && can_control,
+        can_go_previous: capabilities.can_go_previous && can_control,
+        can_play: capabilities.can_play && can_control,
+        can_pause: capabilities.can_pause && can_control,
+        can_seek: capabilities.can_seek && can_control,
+        ..capabilities
+    }
+}
+
+/// Lists the MPRIS capability properties, along with their interface and value.
+fn capability_properties(capabilities: &Capabilities) -> [(&'static str, &'static str, bool); 10] {
+    [
+        (ROOT_INTERFACE, "CanQuit", capabilities.can_quit),
+        (ROOT_INTERFACE, "CanRaise", capabilities.can_raise),
+        (
+            ROOT_INTERFACE,
+            "CanSetFullscreen",
+            capabilities.can_set_fullscreen,
+        ),
+        (PLAYER_INTERFACE, "CanControl", capabilities.can_control),
+        (PLAYER_INTERFACE, "CanGoNext", capabilities.can_go_next),
+        (
+            PLAYER_INTERFACE,
+            "CanGoPrevious",
+            capabilities.can_go_previous,
+        ),
+        (PLAYER_INTERFACE, "CanPlay", capabilities.can_play),
+        (PLAYER_INTERFACE, "CanPause", capabilities.can_pause),
+        (PLAYER_INTERFACE, "CanSeek", capabilities.can_seek),
+        (
+            TRACKLIST_INTERFACE,
+            "CanEditTracks",
+            capabilities.can_edit_tracks,
+        ),
+    ]
+}
+
+/// Lists the capability properties whose value differs between `old` and `new`.
+fn changed_capabilities(
+    old: &Capabilities,
+    new: &Capabilities,
+) -> Vec<(&'static str, &'static str, bool)> {
+    let old = capability_properties(old);
+    let new = capability_properties(new);
+
+    old.iter()
+        .zip(new.iter())
+        .filter(|(old, new)| old != new)
+        .map(|(_, new)| *new)
+        .collect()
+}
+
+/// Checks whether a request from a client is allowed by the capabilities. As required by
+/// the MPRIS specification, calling an unsupported method has no effect.
+fn is_event_allowed(capabilities: &Capabilities, event: &MediaControlEvent) -> bool {
+    use MediaControlEvent::*;
+
+    match event {
+        Next => capabilities.can_go_next,
+        Previous => capabilities.can_go_previous,
+        Play => capabilities.can_play,
+        Pause | Toggle => capabilities.can_pause,
+        Seek(_) | SeekBy(..) | SetPosition(_) => capabilities.can_seek,
+        Stop | SetVolume(_) | OpenUri(_) | SetShuffle(_) | SetLoopStatus(_) | SetRate(_) => {
+            capabilities.can_control
+        }
+        AddTrack { .. } | RemoveTrack(_) => capabilities.can_edit_tracks,
+        GoTo(_) | ActivatePlaylist(_) => true,
+        Raise => capabilities.can_raise,
+        Quit => capabilities.can_quit,
+        SetFullscreen(_) => capabilities.can_set_fullscreen,
+        NameLost | NameAcquired | Disconnected | Reconnected => true,
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct OwnedPlaylist {
+    id: String,
+    name: String,
+    icon: String,
+}
+
+impl From<&MediaPlaylist<'_>> for OwnedPlaylist {
+    fn from(other: &MediaPlaylist) -> Self {
+        OwnedPlaylist {
+            id: other.id.to_string(),
+            name: other.name.to_string(),
+            icon: other.icon.unwrap_or_default().to_string(),
+        }
+    }
+}
+
+fn loop_status_name(loop_status: LoopStatus) -> &'static str {
+    match loop_status {
+        LoopStatus::None => "None",
+        LoopStatus::Track => "Track",
+        LoopStatus::Playlist => "Playlist",
+    }
+}
+
+fn parse_loop_status(name: &str) -> Option<LoopStatus> {
+    match name {
+        "None" => Some(LoopStatus::None),
+        "Track" => Some(LoopStatus::Track),
+        "Playlist" => Some(LoopStatus::Playlist),
+        _ => None,
+    }
+}
+
+fn ordering_name(ordering: PlaylistOrdering) -> &'static str {
+    match ordering {
+        PlaylistOrdering::Alphabetical => "Alphabetical",
+        PlaylistOrdering::CreationDate => "Created",
+        PlaylistOrdering::ModifiedDate => "Modified",
+        PlaylistOrdering::LastPlayDate => "Played",
+        PlaylistOrdering::UserDefined => "User",
+    }
+}
+
+/// Returns the slice of playlists requested by a `GetPlaylists` call.
+///
+/// Souvlaki doesn't know about the dates of the playlists, so only the alphabetical
+/// ordering is actually applied. Every other ordering keeps the order in which the
+/// playlists were set by the user.
+fn get_playlists<'a>(
+    playlists: &'a [OwnedPlaylist],
+    index: u32,
+    max_count: u32,
+    order: &str,
+    reverse_order: bool,
+) -> Vec<&'a OwnedPlaylist> {
+    let mut playlists: Vec<&OwnedPlaylist> = playlists.iter().collect();
+
+    if order == ordering_name(PlaylistOrdering::Alphabetical) {
+        playlists.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    if reverse_order {
+        playlists.reverse();
+    }
+
+    playlists
+        .into_iter()
+        .skip(index as usize)
+        .take(max_count as usize)
+        .collect()
 }
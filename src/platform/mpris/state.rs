@@ -0,0 +1,842 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::{TryFrom, TryInto};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::{
+    capability_properties, changed_capabilities, effective_capabilities, generate_track_id,
+    is_event_allowed, loop_status_name, ordering_name, rate_event, validate_custom_metadata,
+    validate_playlist_id, validate_rate, validate_rate_range, validate_track_id, Error,
+    OwnedPlaylist, RootProperties, NO_TRACK, PLAYER_INTERFACE, PLAYLISTS_INTERFACE, ROOT_INTERFACE,
+    TRACKLIST_INTERFACE,
+};
+use crate::{
+    BoxedEventHandler, Capabilities, EventError, LoopStatus, MediaControlEvent, MediaMetadata,
+    MediaPlayback, MediaPlaylist, MediaPosition, OwnedMediaMetadata, OwnedMetadataValue,
+    PlaylistOrdering, SeekDirection, TrackId,
+};
+
+/// An update sent by `MediaControls` to the service thread.
+#[derive(Clone, PartialEq, Debug)]
+pub enum InternalEvent {
+    ChangeMetadata(OwnedMediaMetadata),
+    ChangePlayback(MediaPlayback),
+    ChangeVolume(f64),
+    ChangeShuffle(bool),
+    ChangeLoopStatus(LoopStatus),
+    ChangeRate(f64),
+    ChangeRateRange(f64, f64),
+    ChangeCapabilities(Capabilities),
+    ChangeFullscreen(bool),
+    NotifySeeked(MediaPosition),
+    ChangeTracklist(Vec<(String, OwnedMediaMetadata)>, Option<String>),
+    InsertTrack {
+        id: String,
+        metadata: OwnedMediaMetadata,
+        after: Option<String>,
+    },
+    RemoveTrack(String),
+    ChangeTrackMetadata(String, OwnedMediaMetadata),
+    ChangePlaylists(Vec<OwnedPlaylist>),
+    ChangeActivePlaylist(Option<String>),
+    ChangePlaylistOrderings(Vec<PlaylistOrdering>),
+}
+
+/// The updates whose arguments have to be checked, or converted to owned values. Every
+/// backend builds them here, and only has to send them.
+impl InternalEvent {
+    pub fn change_metadata(metadata: OwnedMediaMetadata) -> Result<Self, Error> {
+        if let Some(track_id) = &metadata.track_id {
+            validate_track_id(track_id)?;
+        }
+        validate_custom_metadata(&metadata.custom)?;
+        Ok(InternalEvent::ChangeMetadata(metadata))
+    }
+
+    pub fn change_rate(rate: f64) -> Result<Self, Error> {
+        validate_rate(rate)?;
+        Ok(InternalEvent::ChangeRate(rate))
+    }
+
+    pub fn change_rate_range(minimum: f64, maximum: f64) -> Result<Self, Error> {
+        validate_rate_range(minimum, maximum)?;
+        Ok(InternalEvent::ChangeRateRange(minimum, maximum))
+    }
+
+    pub fn change_tracklist(
+        tracks: &[(&str, MediaMetadata)],
+        current: Option<&str>,
+    ) -> Result<Self, Error> {
+        let mut ids = HashSet::new();
+        for (id, metadata) in tracks {
+            validate_track_id(id)?;
+            validate_custom_metadata(metadata.custom)?;
+            if !ids.insert(id) {
+                return Err(Error::DuplicateTrackId(id.to_string()));
+            }
+        }
+        if let Some(current) = current {
+            validate_track_id(current)?;
+        }
+
+        let tracks = tracks
+            .iter()
+            .map(|(id, metadata)| (id.to_string(), metadata.clone().into()))
+            .collect();
+        Ok(InternalEvent::ChangeTracklist(
+            tracks,
+            current.map(|s| s.to_string()),
+        ))
+    }
+
+    pub fn insert_track(
+        id: &str,
+        metadata: MediaMetadata,
+        after: Option<&str>,
+    ) -> Result<Self, Error> {
+        validate_track_id(id)?;
+        validate_custom_metadata(metadata.custom)?;
+        Ok(InternalEvent::InsertTrack {
+            id: id.to_string(),
+            metadata: metadata.into(),
+            after: after.map(|s| s.to_string()),
+        })
+    }
+
+    pub fn remove_track(id: &str) -> Result<Self, Error> {
+        validate_track_id(id)?;
+        Ok(InternalEvent::RemoveTrack(id.to_string()))
+    }
+
+    pub fn change_track_metadata(id: &str, metadata: MediaMetadata) -> Result<Self, Error> {
+        validate_track_id(id)?;
+        validate_custom_metadata(metadata.custom)?;
+        Ok(InternalEvent::ChangeTrackMetadata(
+            id.to_string(),
+            metadata.into(),
+        ))
+    }
+
+    pub fn change_playlists(playlists: &[MediaPlaylist]) -> Result<Self, Error> {
+        for playlist in playlists {
+            validate_playlist_id(playlist.id)?;
+        }
+        let playlists = playlists.iter().map(OwnedPlaylist::from).collect();
+        Ok(InternalEvent::ChangePlaylists(playlists))
+    }
+
+    pub fn change_active_playlist(id: Option<&str>) -> Self {
+        InternalEvent::ChangeActivePlaylist(id.map(|s| s.to_string()))
+    }
+
+    pub fn change_playlist_orderings(orderings: &[PlaylistOrdering]) -> Self {
+        InternalEvent::ChangePlaylistOrderings(orderings.to_vec())
+    }
+}
+
+/// A property value, signal argument or metadata entry, which each backend converts to
+/// the types of its D-Bus library.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MprisValue {
+    Bool(bool),
+    Int32(i32),
+    Int64(i64),
+    UInt32(u32),
+    Double(f64),
+    String(String),
+    ObjectPath(String),
+    StringList(Vec<String>),
+    /// The `(b(oss))` struct of the `ActivePlaylist` property.
+    ActivePlaylist(Option<OwnedPlaylist>),
+    /// An `a{sv}` metadata dict.
+    Metadata(MetadataDict),
+}
+
+pub type MetadataDict = HashMap<String, MprisValue>;
+
+/// A signal to be emitted after a state change.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Signal {
+    Seeked(i64),
+    TrackListReplaced {
+        tracks: Vec<String>,
+        current: String,
+    },
+    TrackAdded {
+        metadata: MetadataDict,
+        after: String,
+    },
+    TrackRemoved(String),
+    TrackMetadataChanged {
+        id: String,
+        metadata: MetadataDict,
+    },
+    PlaylistChanged(OwnedPlaylist),
+}
+
+/// What has to be announced to clients after applying an [`InternalEvent`].
+#[derive(Default, Debug)]
+pub struct Changes {
+    /// The interface and name of the properties whose value changed.
+    pub properties: Vec<(&'static str, &'static str)>,
+    pub signals: Vec<Signal>,
+}
+
+impl Changes {
+    fn property(&mut self, interface: &'static str, name: &'static str) {
+        if !self.properties.contains(&(interface, name)) {
+            self.properties.push((interface, name));
+        }
+    }
+
+    fn property_if(&mut self, changed: bool, interface: &'static str, name: &'static str) {
+        if changed {
+            self.property(interface, name);
+        }
+    }
+}
+
+/// The signals announcing an update to clients, in the order they have to be emitted.
+#[derive(Debug)]
+pub struct Notifications {
+    pub signals: Vec<Signal>,
+    pub properties_changed: Vec<PropertiesChanged>,
+}
+
+impl Notifications {
+    /// Whether the update didn't change anything clients can see.
+    pub fn is_empty(&self) -> bool {
+        self.signals.is_empty() && self.properties_changed.is_empty()
+    }
+}
+
+/// The contents of a `PropertiesChanged` signal.
+#[derive(Debug)]
+pub struct PropertiesChanged {
+    pub interface: &'static str,
+    pub changed: Vec<(&'static str, MprisValue)>,
+    pub invalidated: Vec<&'static str>,
+}
+
+/// The state published by the MPRIS service.
+#[derive(Debug)]
+pub struct ServiceState {
+    pub metadata: OwnedMediaMetadata,
+    /// The id of the current track. It's either the one given in the metadata or a
+    /// generated one.
+    pub track_id: String,
+    pub metadata_dict: MetadataDict,
+    pub playback_status: MediaPlayback,
+    /// When the progress in `playback_status` was reported, to extrapolate the position.
+    pub progress_reported_at: Instant,
+    pub volume: f64,
+    pub shuffle: bool,
+    pub loop_status: LoopStatus,
+    pub rate: f64,
+    pub minimum_rate: f64,
+    pub maximum_rate: f64,
+    pub capabilities: Capabilities,
+    pub root_properties: RootProperties,
+    pub fullscreen: bool,
+    /// The tracks in the tracklist along with their id. `None` if the tracklist
+    /// has never been set by the user.
+    pub tracklist: Option<Vec<(String, OwnedMediaMetadata)>>,
+    pub playlists: Vec<OwnedPlaylist>,
+    pub active_playlist: Option<String>,
+    pub playlist_orderings: Vec<PlaylistOrdering>,
+}
+
+impl ServiceState {
+    pub fn new(capabilities: Capabilities, root_properties: RootProperties) -> Self {
+        Self {
+            metadata: Default::default(),
+            track_id: NO_TRACK.to_owned(),
+            metadata_dict: create_metadata_dict(NO_TRACK, &Default::default()),
+            playback_status: MediaPlayback::Stopped,
+            progress_reported_at: Instant::now(),
+            volume: 1.0,
+            shuffle: false,
+            loop_status: LoopStatus::None,
+            rate: 1.0,
+            minimum_rate: 1.0,
+            maximum_rate: 1.0,
+            capabilities: effective_capabilities(capabilities),
+            root_properties,
+            fullscreen: false,
+            tracklist: None,
+            playlists: Vec::new(),
+            active_playlist: None,
+            playlist_orderings: vec![PlaylistOrdering::UserDefined],
+        }
+    }
+
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) {
+        self.track_id = match &metadata.track_id {
+            Some(track_id) => track_id.clone(),
+            // Keep the generated id if the same metadata is set again.
+            None if metadata == self.metadata => self.track_id.clone(),
+            None => generate_track_id(),
+        };
+        self.metadata_dict = create_metadata_dict(&self.track_id, &metadata);
+        self.metadata = metadata;
+    }
+
+    /// Updates the state, and returns the signals which announce the update to clients.
+    pub fn update(&mut self, event: InternalEvent) -> Result<Notifications, Error> {
+        let changes = self.apply(event)?;
+        let properties_changed = self.properties_changed(&changes);
+
+        Ok(Notifications {
+            signals: changes.signals,
+            properties_changed,
+        })
+    }
+
+    /// The metadata of the current track, with the id under which it is published.
+    pub fn published_metadata(&self) -> OwnedMediaMetadata {
+        OwnedMediaMetadata {
+            track_id: (self.track_id != NO_TRACK).then(|| self.track_id.clone()),
+            ..self.metadata.clone()
+        }
+    }
+
+    /// Updates the state and lists the properties and signals clients have to be
+    /// notified of. The state is left as it was if the update doesn't fit the tracklist.
+    fn apply(&mut self, event: InternalEvent) -> Result<Changes, Error> {
+        let mut changes = Changes::default();
+
+        match event {
+            InternalEvent::ChangeMetadata(metadata) => {
+                let old_metadata_dict = self.metadata_dict.clone();
+                self.set_metadata(metadata);
+                changes.property_if(
+                    self.metadata_dict != old_metadata_dict,
+                    PLAYER_INTERFACE,
+                    "Metadata",
+                );
+            }
+            InternalEvent::ChangePlayback(playback) => {
+                // Only the status is a property, the progress is read through `Position`.
+                let status_changed = std::mem::discriminant(&self.playback_status)
+                    != std::mem::discriminant(&playback);
+                self.playback_status = playback;
+                self.progress_reported_at = Instant::now();
+                changes.property_if(status_changed, PLAYER_INTERFACE, "PlaybackStatus");
+            }
+            InternalEvent::ChangeVolume(volume) => {
+                changes.property_if(self.volume != volume, PLAYER_INTERFACE, "Volume");
+                self.volume = volume;
+            }
+            InternalEvent::ChangeShuffle(shuffle) => {
+                changes.property_if(self.shuffle != shuffle, PLAYER_INTERFACE, "Shuffle");
+                self.shuffle = shuffle;
+            }
+            InternalEvent::ChangeLoopStatus(loop_status) => {
+                changes.property_if(
+                    self.loop_status != loop_status,
+                    PLAYER_INTERFACE,
+                    "LoopStatus",
+                );
+                self.loop_status = loop_status;
+            }
+            InternalEvent::ChangeRate(rate) => {
+                // The progress made so far was made at the previous rate.
+                self.playback_status = self.current_playback();
+                self.progress_reported_at = Instant::now();
+                changes.property_if(self.rate != rate, PLAYER_INTERFACE, "Rate");
+                self.rate = rate;
+            }
+            InternalEvent::ChangeRateRange(minimum, maximum) => {
+                changes.property_if(
+                    self.minimum_rate != minimum,
+                    PLAYER_INTERFACE,
+                    "MinimumRate",
+                );
+                changes.property_if(
+                    self.maximum_rate != maximum,
+                    PLAYER_INTERFACE,
+                    "MaximumRate",
+                );
+                self.minimum_rate = minimum;
+                self.maximum_rate = maximum;
+            }
+            InternalEvent::ChangeCapabilities(capabilities) => {
+                let capabilities = effective_capabilities(capabilities);
+                for (interface, name, _) in changed_capabilities(&self.capabilities, &capabilities)
+                {
+                    changes.property(interface, name);
+                }
+                self.capabilities = capabilities;
+            }
+            InternalEvent::ChangeFullscreen(fullscreen) => {
+                changes.property_if(self.fullscreen != fullscreen, ROOT_INTERFACE, "Fullscreen");
+                self.fullscreen = fullscreen;
+            }
+            InternalEvent::NotifySeeked(position) => {
+                if let MediaPlayback::Playing { progress } | MediaPlayback::Paused { progress } =
+                    &mut self.playback_status
+                {
+                    *progress = Some(position);
+                }
+                self.progress_reported_at = Instant::now();
+                changes.signals.push(Signal::Seeked(micros(position.0)));
+            }
+            InternalEvent::ChangeTracklist(tracks, current) => {
+                changes.signals.push(Signal::TrackListReplaced {
+                    tracks: tracks.iter().map(|(id, _)| id.clone()).collect(),
+                    current: current.unwrap_or_else(|| NO_TRACK.to_owned()),
+                });
+                self.set_tracklist(tracks, &mut changes);
+            }
+            InternalEvent::InsertTrack {
+                id,
+                metadata,
+                after,
+            } => {
+                if self.track_index(&id).is_ok() {
+                    return Err(Error::DuplicateTrackId(id));
+                }
+                let index = match &after {
+                    Some(after) => self.track_index(after)? + 1,
+                    None => 0,
+                };
+                let mut tracks = self.tracklist.clone().unwrap_or_default();
+
+                changes.signals.push(Signal::TrackAdded {
+                    metadata: create_metadata_dict(&id, &metadata),
+                    after: after.unwrap_or_else(|| NO_TRACK.to_owned()),
+                });
+                tracks.insert(index, (id, metadata));
+                self.set_tracklist(tracks, &mut changes);
+            }
+            InternalEvent::RemoveTrack(id) => {
+                let index = self.track_index(&id)?;
+                if let Some(tracklist) = &mut self.tracklist {
+                    tracklist.remove(index);
+                }
+                changes.signals.push(Signal::TrackRemoved(id));
+                changes.property(TRACKLIST_INTERFACE, "Tracks");
+            }
+            InternalEvent::ChangeTrackMetadata(id, metadata) => {
+                let index = self.track_index(&id)?;
+                changes.signals.push(Signal::TrackMetadataChanged {
+                    metadata: create_metadata_dict(&id, &metadata),
+                    id,
+                });
+                if let Some(tracklist) = &mut self.tracklist {
+                    tracklist[index].1 = metadata;
+                }
+            }
+            InternalEvent::ChangePlaylists(playlists) => {
+                let old_active_playlist = self.get_active_playlist().cloned();
+
+                // Only the playlists which were kept but renamed or given another icon
+                // are considered changed.
+                for playlist in &playlists {
+                    let changed = self
+                        .playlists
+                        .iter()
+                        .any(|old| old.id == playlist.id && old != playlist);
+                    if changed {
+                        changes
+                            .signals
+                            .push(Signal::PlaylistChanged(playlist.clone()));
+                    }
+                }
+
+                if playlists.len() != self.playlists.len() {
+                    changes.property(PLAYLISTS_INTERFACE, "PlaylistCount");
+                }
+                self.playlists = playlists;
+
+                if self.get_active_playlist() != old_active_playlist.as_ref() {
+                    changes.property(PLAYLISTS_INTERFACE, "ActivePlaylist");
+                }
+            }
+            InternalEvent::ChangeActivePlaylist(id) => {
+                let old_active_playlist = self.get_active_playlist().cloned();
+                self.active_playlist = id;
+                changes.property_if(
+                    self.get_active_playlist() != old_active_playlist.as_ref(),
+                    PLAYLISTS_INTERFACE,
+                    "ActivePlaylist",
+                );
+            }
+            InternalEvent::ChangePlaylistOrderings(orderings) => {
+                changes.property_if(
+                    self.playlist_orderings != orderings,
+                    PLAYLISTS_INTERFACE,
+                    "Orderings",
+                );
+                self.playlist_orderings = orderings;
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// The position of the track `id` in the tracklist.
+    fn track_index(&self, id: &str) -> Result<usize, Error> {
+        self.tracklist
+            .iter()
+            .flatten()
+            .position(|(i, _)| i == id)
+            .ok_or_else(|| Error::UnknownTrackId(id.to_owned()))
+    }
+
+    fn set_tracklist(&mut self, tracks: Vec<(String, OwnedMediaMetadata)>, changes: &mut Changes) {
+        if self.tracklist.is_none() {
+            changes.property(ROOT_INTERFACE, "HasTracklist");
+        }
+        self.tracklist = Some(tracks);
+        changes.property(TRACKLIST_INTERFACE, "Tracks");
+    }
+
+    /// Groups the changed properties by interface, along with their new value.
+    fn properties_changed(&self, changes: &Changes) -> Vec<PropertiesChanged> {
+        let interfaces = [
+            ROOT_INTERFACE,
+            PLAYER_INTERFACE,
+            TRACKLIST_INTERFACE,
+            PLAYLISTS_INTERFACE,
+        ];
+
+        interfaces
+            .iter()
+            .map(|&interface| {
+                let mut properties = PropertiesChanged {
+                    interface,
+                    changed: Vec::new(),
+                    invalidated: Vec::new(),
+                };
+                for &(_, name) in changes.properties.iter().filter(|(i, _)| *i == interface) {
+                    match self.property_value(name) {
+                        Some(value) => properties.changed.push((name, value)),
+                        None => properties.invalidated.push(name),
+                    }
+                }
+                properties
+            })
+            .filter(|p| !p.changed.is_empty() || !p.invalidated.is_empty())
+            .collect()
+    }
+
+    /// Returns the value of a property which can change, or `None` if its changes are
+    /// announced without the value.
+    fn property_value(&self, name: &str) -> Option<MprisValue> {
+        let value = match name {
+            "Metadata" => MprisValue::Metadata(self.metadata_dict.clone()),
+            "PlaybackStatus" => MprisValue::String(self.playback_status_name().to_owned()),
+            "Volume" => MprisValue::Double(self.volume),
+            "Shuffle" => MprisValue::Bool(self.shuffle),
+            "LoopStatus" => MprisValue::String(loop_status_name(self.loop_status).to_owned()),
+            "Rate" => MprisValue::Double(self.rate),
+            "MinimumRate" => MprisValue::Double(self.minimum_rate),
+            "MaximumRate" => MprisValue::Double(self.maximum_rate),
+            "Fullscreen" => MprisValue::Bool(self.fullscreen),
+            "HasTracklist" => MprisValue::Bool(self.tracklist.is_some()),
+            "Tracks" => return None,
+            "PlaylistCount" => MprisValue::UInt32(self.playlists.len() as u32),
+            "ActivePlaylist" => MprisValue::ActivePlaylist(self.get_active_playlist().cloned()),
+            "Orderings" => MprisValue::StringList(self.playlist_ordering_names()),
+            _ => {
+                let &(_, _, value) = capability_properties(&self.capabilities)
+                    .iter()
+                    .find(|(_, capability, _)| *capability == name)?;
+                MprisValue::Bool(value)
+            }
+        };
+        Some(value)
+    }
+
+    pub fn get_track(&self, id: &str) -> Option<&OwnedMediaMetadata> {
+        self.tracklist
+            .iter()
+            .flatten()
+            .find(|(track_id, _)| track_id == id)
+            .map(|(_, metadata)| metadata)
+    }
+
+    pub fn track_ids(&self) -> Vec<String> {
+        self.tracklist
+            .iter()
+            .flatten()
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    pub fn get_active_playlist(&self) -> Option<&OwnedPlaylist> {
+        let id = self.active_playlist.as_ref()?;
+        self.playlists.iter().find(|p| p.id == *id)
+    }
+
+    pub fn playlist_ordering_names(&self) -> Vec<String> {
+        self.playlist_orderings
+            .iter()
+            .map(|ordering| ordering_name(*ordering).to_owned())
+            .collect()
+    }
+
+    pub fn playback_status_name(&self) -> &'static str {
+        match self.playback_status {
+            MediaPlayback::Playing { .. } => "Playing",
+            MediaPlayback::Paused { .. } => "Paused",
+            MediaPlayback::Stopped => "Stopped",
+        }
+    }
+
+    /// The position of the playback in microseconds.
+    pub fn position(&self) -> i64 {
+        self.current_progress().map_or(0, micros)
+    }
+
+    /// The playback status, with the progress extrapolated to the current time.
+    pub fn current_playback(&self) -> MediaPlayback {
+        let progress = self.current_progress().map(MediaPosition);
+        match self.playback_status {
+            MediaPlayback::Playing { .. } => MediaPlayback::Playing { progress },
+            MediaPlayback::Paused { .. } => MediaPlayback::Paused { progress },
+            MediaPlayback::Stopped => MediaPlayback::Stopped,
+        }
+    }
+
+    /// The last reported progress, plus the time which has passed since then at the current
+    /// rate if playing, clamped to the duration of the track.
+    fn current_progress(&self) -> Option<Duration> {
+        let progress = match self.playback_status {
+            MediaPlayback::Playing {
+                progress: Some(progress),
+            } => {
+                let elapsed = self.progress_reported_at.elapsed().as_secs_f64() * self.rate;
+                let elapsed = Duration::try_from_secs_f64(elapsed).unwrap_or(Duration::MAX);
+                progress.0.saturating_add(elapsed)
+            }
+            MediaPlayback::Paused {
+                progress: Some(progress),
+            } => progress.0,
+            _ => return None,
+        };
+
+        Some(match self.metadata.duration {
+            Some(duration) => progress.min(duration),
+            None => progress,
+        })
+    }
+
+    /// Converts a `SetPosition` call into an event. The state has to be unlocked before
+    /// passing it to the event handler, which might need to access it.
+    pub fn set_position_event(&self, track_id: &str, position: i64) -> Option<MediaControlEvent> {
+        // According to the MPRIS specification:
+
+        // If the TrackId argument is not the same as the current
+        // trackid, the call is ignored as stale.
+        if track_id != self.track_id {
+            return None;
+        }
+
+        if let Some(duration) = self.metadata.duration {
+            // If the Position argument is greater than the track length, do nothing.
+            if position > micros(duration) {
+                return None;
+            }
+        }
+
+        // If the Position argument is less than 0, do nothing.
+        let position = u64::try_from(position).ok()?;
+        Some(MediaControlEvent::SetPosition(MediaPosition(
+            Duration::from_micros(position),
+        )))
+    }
+
+    /// Converts a request to change the `Rate` property into an event.
+    pub fn rate_event(&self, rate: f64) -> Option<MediaControlEvent> {
+        rate_event(rate, self.minimum_rate, self.maximum_rate)
+    }
+}
+
+/// The event handler of a service, which is kept by the backend to restart the service.
+pub type SharedEventHandler = Arc<Mutex<BoxedEventHandler>>;
+
+/// Sends an event about the service itself, such as a lost connection, to the handler. Its
+/// result is ignored, since no client is waiting for a reply.
+pub fn notify(event_handler: &SharedEventHandler, event: MediaControlEvent) {
+    (event_handler.lock().unwrap())(event).ok();
+}
+
+/// Wraps the event handler so that requests for actions which aren't supported have
+/// no effect, and requests for URIs which can't be opened are rejected.
+pub fn filter_events(
+    state: &Arc<Mutex<ServiceState>>,
+    event_handler: SharedEventHandler,
+) -> impl Fn(MediaControlEvent) -> Result<(), EventError> + Send + Sync + 'static {
+    let state = state.clone();
+
+    move |event| {
+        let (is_allowed, is_uri_supported) = {
+            let state = state.lock().unwrap();
+            let is_uri_supported = match &event {
+                MediaControlEvent::OpenUri(uri) | MediaControlEvent::AddTrack { uri, .. } => {
+                    state.root_properties.supports_uri(uri)
+                }
+                _ => true,
+            };
+            (
+                is_event_allowed(&state.capabilities, &event),
+                is_uri_supported,
+            )
+        };
+
+        if !is_allowed {
+            // The MPRIS specification asks for such calls to be ignored, not rejected.
+            Ok(())
+        } else if !is_uri_supported {
+            Err(EventError::NotSupported)
+        } else {
+            let mut event_handler = event_handler.lock().unwrap();
+            event_handler(event)
+        }
+    }
+}
+
+/// Converts a `Seek` call into an event.
+pub fn seek_event(offset: i64) -> MediaControlEvent {
+    let direction = if offset > 0 {
+        SeekDirection::Forward
+    } else {
+        SeekDirection::Backward
+    };
+
+    MediaControlEvent::SeekBy(direction, Duration::from_micros(offset.unsigned_abs()))
+}
+
+/// Converts an `AddTrack` call into an event.
+pub fn add_track_event(uri: String, after_track: &str, set_as_current: bool) -> MediaControlEvent {
+    let after = if after_track == NO_TRACK {
+        None
+    } else {
+        Some(TrackId(after_track.to_owned()))
+    };
+
+    MediaControlEvent::AddTrack {
+        uri,
+        after,
+        set_as_current,
+    }
+}
+
+/// Converts a duration to the microseconds used by MPRIS, saturating on overflow.
+fn micros(duration: Duration) -> i64 {
+    duration.as_micros().try_into().unwrap_or(i64::MAX)
+}
+
+pub fn create_metadata_dict(track_id: &str, metadata: &OwnedMediaMetadata) -> MetadataDict {
+    let mut dict = MetadataDict::new();
+
+    let mut insert = |k: &str, v| dict.insert(k.to_string(), v);
+
+    let OwnedMediaMetadata {
+        track_id: _,
+        ref title,
+        ref album,
+        ref artist,
+        ref artists,
+        ref cover_url,
+        ref duration,
+        ref album_artists,
+        ref composers,
+        ref lyricists,
+        ref genres,
+        ref comments,
+        ref track_number,
+        ref disc_number,
+        ref audio_bpm,
+        ref content_created,
+        ref first_used,
+        ref last_used,
+        ref use_count,
+        ref user_rating,
+        ref auto_rating,
+        ref url,
+        ref lyrics,
+        ref custom,
+    } = metadata;
+
+    // MPRIS
+    insert("mpris:trackid", MprisValue::ObjectPath(track_id.to_owned()));
+
+    if let Some(length) = duration {
+        insert("mpris:length", MprisValue::Int64(micros(*length)));
+    }
+    if let Some(cover_url) = cover_url {
+        insert("mpris:artUrl", MprisValue::String(cover_url.clone()));
+    }
+
+    // Xesam
+    let strings = [
+        ("xesam:title", title),
+        ("xesam:album", album),
+        ("xesam:contentCreated", content_created),
+        ("xesam:firstUsed", first_used),
+        ("xesam:lastUsed", last_used),
+        ("xesam:url", url),
+        ("xesam:asText", lyrics),
+    ];
+    for (key, value) in strings {
+        if let Some(value) = value {
+            insert(key, MprisValue::String(value.clone()));
+        }
+    }
+
+    // `artist` is only used when there isn't a list of artists.
+    let artist: Vec<String> = artist.iter().cloned().collect();
+    let artists = if artists.is_empty() { &artist } else { artists };
+    let lists = [
+        ("xesam:artist", artists),
+        ("xesam:albumArtist", album_artists),
+        ("xesam:composer", composers),
+        ("xesam:lyricist", lyricists),
+        ("xesam:genre", genres),
+        ("xesam:comment", comments),
+    ];
+    for (key, value) in lists {
+        if !value.is_empty() {
+            insert(key, MprisValue::StringList(value.clone()));
+        }
+    }
+
+    let integers = [
+        ("xesam:trackNumber", track_number),
+        ("xesam:discNumber", disc_number),
+        ("xesam:audioBPM", audio_bpm),
+        ("xesam:useCount", use_count),
+    ];
+    for (key, value) in integers {
+        if let Some(value) = value {
+            insert(key, MprisValue::Int32(*value));
+        }
+    }
+
+    let floats = [
+        ("xesam:userRating", user_rating),
+        ("xesam:autoRating", auto_rating),
+    ];
+    for (key, value) in floats {
+        if let Some(value) = value {
+            insert(key, MprisValue::Double(*value));
+        }
+    }
+
+    // Custom entries
+    for (key, value) in custom {
+        let value = match value {
+            OwnedMetadataValue::String(value) => MprisValue::String(value.clone()),
+            OwnedMetadataValue::Integer(value) => MprisValue::Int64(*value),
+            OwnedMetadataValue::Float(value) => MprisValue::Double(*value),
+            OwnedMetadataValue::Bool(value) => MprisValue::Bool(*value),
+            OwnedMetadataValue::StringList(list) => MprisValue::StringList(list.clone()),
+        };
+        insert(key, value);
+    }
+
+    dict
+}
@@ -1,173 +1,641 @@
 use std::collections::HashMap;
-use std::convert::From;
 use std::convert::TryFrom;
-use std::convert::TryInto;
-use std::sync::{mpsc, Arc, Mutex};
+use std::fmt::Write;
+use std::future::{poll_fn, Future};
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::Poll;
 use std::thread::{self, JoinHandle};
 use std::time::Duration;
 
-use zbus::{dbus_interface, ConnectionBuilder, SignalContext};
-use zvariant::{ObjectPath, Value};
+use zbus::export::futures_util::future::{self, Either};
+use zbus::export::futures_util::{stream, StreamExt};
+use zbus::fdo::{RequestNameFlags, RequestNameReply};
+use zbus::names::MemberName;
+use zbus::names::{ErrorName, InterfaceName};
+use zbus::{
+    dbus_interface, fdo, Connection, ConnectionBuilder, DBusError, DispatchResult, Interface,
+    Message, MessageBuilder, MessageHeader, ObjectServer, SignalContext,
+};
+use zvariant::{ObjectPath, OwnedValue, Value};
 
+use crate::controls::{box_event_handler, panic_message};
 use crate::{
-    MediaControlEvent, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig, SeekDirection,
+    BoxedEventHandler, Capabilities, DbusConnection, EventError, EventResult, LoopStatus,
+    MediaControlEvent, MediaControlsBackend, MediaMetadata, MediaPlayback, MediaPlaylist,
+    MediaPosition, OwnedMediaMetadata, PlatformConfig, PlaylistId, PlaylistOrdering, TrackId,
 };
 
-use super::Error;
+use super::state::{
+    add_track_event, create_metadata_dict, filter_events, notify, seek_event, InternalEvent,
+    MetadataDict, MprisValue, Notifications, ServiceState, SharedEventHandler, Signal,
+};
+use super::{
+    bus_name, get_playlists, loop_status_name, method_error_name, parse_loop_status, Backoff,
+    Error, OwnedPlaylist, RootProperties, OBJECT_PATH,
+};
 
-/// A handle to OS media controls.
-pub struct MediaControls {
-    thread: Option<ServiceThreadHandle>,
-    dbus_name: String,
+/// The MPRIS backend built on `zbus`, a pure Rust D-Bus implementation.
+pub struct ZbusBackend {
+    service: Option<ServiceHandle>,
+    state: Arc<Mutex<ServiceState>>,
+    dbus_connection: DbusConnection,
+    bus_name: String,
     friendly_name: String,
+    capabilities: Capabilities,
+    root_properties: RootProperties,
 }
 
-struct ServiceThreadHandle {
-    event_channel: mpsc::Sender<InternalEvent>,
-    thread: JoinHandle<()>,
+struct ServiceHandle {
+    event_channel: async_channel::Sender<Notifications>,
+    /// The thread running the service, or `None` if the service future was handed to
+    /// the application.
+    thread: Option<ServiceThread>,
 }
 
-#[derive(Clone, PartialEq, Debug)]
-enum InternalEvent {
-    ChangeMetadata(OwnedMetadata),
-    ChangePlayback(MediaPlayback),
-    ChangeVolume(f64),
-    Kill,
+/// What the service needs to publish the controls, across reconnections.
+struct Service {
+    dbus_connection: DbusConnection,
+    bus_name: String,
+    friendly_name: String,
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: SharedEventHandler,
+    event_channel: async_channel::Receiver<Notifications>,
+    internal_executor: bool,
 }
 
-#[derive(Clone, Debug)]
-struct ServiceState {
-    metadata: OwnedMetadata,
-    playback_status: MediaPlayback,
-    volume: f64,
-}
+impl Service {
+    async fn connect(&self) -> Result<(Connection, NameChanges), Error> {
+        connect(
+            &self.dbus_connection,
+            &self.bus_name,
+            self.friendly_name.clone(),
+            &self.state,
+            self.event_handler.clone(),
+            self.internal_executor,
+        )
+        .await
+    }
 
-#[derive(Clone, PartialEq, Eq, Debug, Default)]
-struct OwnedMetadata {
-    pub title: Option<String>,
-    pub album: Option<String>,
-    pub artist: Option<String>,
-    pub cover_url: Option<String>,
-    pub duration: Option<i64>,
-}
+    /// Serves the controls on `connection`, and reconnects with backoff whenever the
+    /// connection is lost, until the controls are detached.
+    async fn supervise(
+        &self,
+        mut connection: Connection,
+        mut name_changes: NameChanges,
+    ) -> Result<(), Error> {
+        loop {
+            let result = run_service(
+                &connection,
+                &self.bus_name,
+                &self.event_channel,
+                name_changes,
+                self.internal_executor,
+            )
+            .await;
+            let err = match result {
+                Ok(()) => return Ok(()),
+                Err(err) => err,
+            };
+            notify(&self.event_handler, MediaControlEvent::Disconnected);
+            if let DbusConnection::Zbus(_) = self.dbus_connection {
+                // The connection of the application can't be replaced.
+                return Err(err.into());
+            }
 
-impl From<MediaMetadata<'_>> for OwnedMetadata {
-    fn from(other: MediaMetadata) -> Self {
-        OwnedMetadata {
-            title: other.title.map(|s| s.to_string()),
-            artist: other.artist.map(|s| s.to_string()),
-            album: other.album.map(|s| s.to_string()),
-            cover_url: other.cover_url.map(|s| s.to_string()),
-            duration: other.duration.map(|d| d.as_micros().try_into().unwrap()),
+            let mut backoff = Backoff::new();
+            (connection, name_changes) = loop {
+                if !self.wait(backoff.next_delay()).await {
+                    return Ok(());
+                }
+                match self.connect().await {
+                    Ok(service) => break service,
+                    Err(err @ Error::NameTaken(_)) => return Err(err),
+                    Err(_) => continue,
+                }
+            };
+            notify(&self.event_handler, MediaControlEvent::Reconnected);
         }
     }
+
+    /// Waits for `delay`, dropping the updates sent meanwhile. Returns `false` if the
+    /// controls were detached.
+    async fn wait(&self, delay: Duration) -> bool {
+        let detached = Box::pin(async { while self.event_channel.recv().await.is_ok() {} });
+        let timer = async_io::Timer::after(delay);
+        matches!(future::select(timer, detached).await, Either::Left(_))
+    }
 }
 
-impl MediaControls {
+struct ServiceThread {
+    handle: JoinHandle<Result<(), Error>>,
+    /// Kept to restart the service thread if it stops.
+    event_handler: SharedEventHandler,
+}
+
+impl ZbusBackend {
     /// Create media controls with the specified config.
     pub fn new(config: PlatformConfig) -> Result<Self, Error> {
+        let root_properties = RootProperties::new(&config);
         let PlatformConfig {
             dbus_name,
+            unique_dbus_name,
+            dbus_connection,
             display_name,
+            capabilities,
             ..
         } = config;
 
         Ok(Self {
-            thread: None,
-            dbus_name: dbus_name.to_string(),
+            service: None,
+            state: Arc::new(Mutex::new(ServiceState::new(
+                capabilities,
+                root_properties.clone(),
+            ))),
+            dbus_connection,
+            bus_name: bus_name(dbus_name, unique_dbus_name),
             friendly_name: display_name.to_string(),
+            capabilities,
+            root_properties,
         })
     }
 
-    /// Attach the media control events to a handler.
-    pub fn attach<F>(&mut self, event_handler: F) -> Result<(), Error>
+    /// Attach the media control events to a handler, without spawning a thread.
+    ///
+    /// The returned future runs the MPRIS service, and must be spawned by the application
+    /// on its own runtime. It completes once the controls are detached or dropped. If the
+    /// connection to the bus is lost, it reconnects like the service thread of
+    /// [`attach`](MediaControlsBackend::attach).
+    pub fn attach_future<F, R>(
+        &mut self,
+        event_handler: F,
+    ) -> Result<impl Future<Output = Result<(), Error>> + Send + 'static, Error>
     where
-        F: Fn(MediaControlEvent) + Send + 'static,
+        F: FnMut(MediaControlEvent) -> R + Send + 'static,
+        R: EventResult,
     {
+        let event_handler = Arc::new(Mutex::new(box_event_handler(event_handler)));
         self.detach()?;
 
-        let dbus_name = self.dbus_name.clone();
-        let friendly_name = self.friendly_name.clone();
-        let event_handler = Arc::new(Mutex::new(event_handler));
-        let (event_channel, rx) = mpsc::channel();
+        let (event_channel, rx) = async_channel::unbounded();
+        self.service = Some(ServiceHandle {
+            event_channel,
+            thread: None,
+        });
 
-        self.thread = Some(ServiceThreadHandle {
+        self.state = Arc::new(Mutex::new(ServiceState::new(
+            self.capabilities,
+            self.root_properties.clone(),
+        )));
+        let service = Service {
+            dbus_connection: self.dbus_connection.clone(),
+            bus_name: self.bus_name.clone(),
+            friendly_name: self.friendly_name.clone(),
+            state: self.state.clone(),
+            event_handler,
+            event_channel: rx,
+            internal_executor: false,
+        };
+        Ok(async move {
+            let (connection, name_changes) = service.connect().await?;
+            service.supervise(connection, name_changes).await
+        })
+    }
+
+    fn send_internal_event(&mut self, event: InternalEvent) -> Result<(), Error> {
+        let service = self.service.as_ref().ok_or(Error::ThreadNotRunning)?;
+        if service.thread.is_none() && service.event_channel.is_closed() {
+            return Err(Error::ServiceStopped);
+        }
+
+        let notifications = self.state.lock().unwrap().update(event)?;
+        if service.event_channel.is_closed() {
+            return self.restart();
+        }
+        if notifications.is_empty() {
+            return Ok(());
+        }
+        match service.event_channel.try_send(notifications) {
+            Ok(()) => Ok(()),
+            Err(_) => self.restart(),
+        }
+    }
+
+    /// Connects to the bus and spawns the service thread.
+    fn start(
+        &self,
+        state: Arc<Mutex<ServiceState>>,
+        event_handler: SharedEventHandler,
+    ) -> Result<ServiceHandle, Error> {
+        let (event_channel, rx) = async_channel::unbounded();
+        let service = Service {
+            dbus_connection: self.dbus_connection.clone(),
+            bus_name: self.bus_name.clone(),
+            friendly_name: self.friendly_name.clone(),
+            state,
+            event_handler: event_handler.clone(),
+            event_channel: rx,
+            internal_executor: true,
+        };
+
+        // Check if the connection can be created BEFORE spawning the new thread
+        let (connection, name_changes) = pollster::block_on(service.connect())?;
+        let handle =
+            thread::spawn(move || pollster::block_on(service.supervise(connection, name_changes)));
+
+        Ok(ServiceHandle {
             event_channel,
-            thread: thread::spawn(move || {
-                pollster::block_on(run_service(dbus_name, friendly_name, event_handler, rx))
-                    .unwrap();
+            thread: Some(ServiceThread {
+                handle,
+                event_handler,
             }),
-        });
+        })
+    }
+
+    /// Replaces a service thread which stopped, e.g. because the bus name was taken while it
+    /// was reconnecting, by a new one which publishes the same state.
+    ///
+    /// A service future handed to the application can't be restarted.
+    fn restart(&mut self) -> Result<(), Error> {
+        let event_handler = match &self.service {
+            Some(ServiceHandle {
+                thread: Some(thread),
+                ..
+            }) => thread.event_handler.clone(),
+            Some(_) => return Err(Error::ServiceStopped),
+            None => return Err(Error::ThreadNotRunning),
+        };
+        let service = self.start(self.state.clone(), event_handler)?;
+        if let Some(ServiceHandle {
+            thread: Some(old_thread),
+            ..
+        }) = self.service.replace(service)
+        {
+            old_thread.handle.join().ok();
+        }
+        Ok(())
+    }
+}
+
+impl MediaControlsBackend for ZbusBackend {
+    /// Attach the media control events to a handler.
+    ///
+    /// If the service thread stops, e.g. because the connection to the bus was lost, it is
+    /// restarted by the next call which updates the controls.
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error> {
+        self.detach()?;
+
+        let state = Arc::new(Mutex::new(ServiceState::new(
+            self.capabilities,
+            self.root_properties.clone(),
+        )));
+        let service = self.start(state.clone(), Arc::new(Mutex::new(event_handler)))?;
+        self.state = state;
+        self.service = Some(service);
         Ok(())
     }
+
     /// Detach the event handler.
-    pub fn detach(&mut self) -> Result<(), Error> {
-        if let Some(ServiceThreadHandle {
+    fn detach(&mut self) -> Result<(), Error> {
+        if let Some(ServiceHandle {
             event_channel,
             thread,
-        }) = self.thread.take()
+        }) = self.service.take()
         {
-            event_channel.send(InternalEvent::Kill).ok();
-            thread.join().map_err(|_| Error::ThreadPanicked)?;
+            // The service stops once the channel is closed.
+            event_channel.close();
+            if let Some(thread) = thread {
+                thread
+                    .handle
+                    .join()
+                    .map_err(|payload| Error::ThreadPanicked(panic_message(&*payload)))??;
+            }
         }
         Ok(())
     }
 
     /// Set the current playback status.
-    pub fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
         self.send_internal_event(InternalEvent::ChangePlayback(playback))?;
         Ok(())
     }
 
     /// Set the metadata of the currently playing media item.
-    pub fn set_metadata(&mut self, metadata: MediaMetadata) -> Result<(), Error> {
-        self.send_internal_event(InternalEvent::ChangeMetadata(metadata.into()))?;
-        Ok(())
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_metadata(metadata)?)
     }
 
     /// Set the volume level (0.0 - 1.0) (Only available on MPRIS)
-    pub fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
+    fn set_volume(&mut self, volume: f64) -> Result<(), Error> {
         self.send_internal_event(InternalEvent::ChangeVolume(volume))?;
         Ok(())
     }
 
-    fn send_internal_event(&mut self, event: InternalEvent) -> Result<(), Error> {
-        let channel = &self
-            .thread
-            .as_ref()
-            .ok_or(Error::ThreadNotRunning)?
-            .event_channel;
-        channel.send(event).map_err(|_| Error::ThreadPanicked)
+    /// Set whether shuffle is enabled.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeShuffle(shuffle))
+    }
+
+    /// Set the loop/repeat status.
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeLoopStatus(loop_status))
+    }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate(rate)?)
+    }
+
+    /// Set the range of playback rates that clients may request. The range must include
+    /// 1.0. By default only 1.0 is allowed. (Only available on MPRIS)
+    fn set_rate_range(&mut self, minimum: f64, maximum: f64) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_rate_range(minimum, maximum)?)
+    }
+
+    /// Set whether the media player is in fullscreen. (Only available on MPRIS)
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::ChangeFullscreen(fullscreen))
+    }
+
+    /// Notify clients that the position jumped to `position`, e.g. after a seek, and update
+    /// the reported position accordingly. (Only available on MPRIS)
+    fn notify_seeked(&mut self, position: MediaPosition) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::NotifySeeked(position))
+    }
+
+    /// Set the actions supported by the media player.
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        self.capabilities = capabilities;
+        self.send_internal_event(InternalEvent::ChangeCapabilities(capabilities))
+    }
+
+    /// Replace the whole tracklist, optionally specifying the id of the current track.
+    /// (Only available on MPRIS)
+    fn set_tracklist(
+        &mut self,
+        tracks: &[(&str, MediaMetadata)],
+        current: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_tracklist(tracks, current)?)
+    }
+
+    /// Insert a track into the tracklist, right after the track `after`.
+    /// If `after` is `None`, the track is inserted at the beginning. (Only available on MPRIS)
+    fn insert_track(
+        &mut self,
+        id: &str,
+        metadata: MediaMetadata,
+        after: Option<&str>,
+    ) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::insert_track(id, metadata, after)?)
+    }
+
+    /// Remove a track from the tracklist. (Only available on MPRIS)
+    fn remove_track(&mut self, id: &str) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::remove_track(id)?)
+    }
+
+    /// Set the metadata of a track in the tracklist. (Only available on MPRIS)
+    fn set_track_metadata(&mut self, id: &str, metadata: MediaMetadata) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_track_metadata(id, metadata)?)
+    }
+
+    /// Set the playlists which can be activated by the user. (Only available on MPRIS)
+    fn set_playlists(&mut self, playlists: &[MediaPlaylist]) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlists(playlists)?)
+    }
+
+    /// Set the id of the currently active playlist, or `None` if there isn't one.
+    /// (Only available on MPRIS)
+    fn set_active_playlist(&mut self, id: Option<&str>) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_active_playlist(id))
+    }
+
+    /// Set the orderings in which the playlists can be listed. (Only available on MPRIS)
+    fn set_playlist_orderings(&mut self, orderings: &[PlaylistOrdering]) -> Result<(), Error> {
+        self.send_internal_event(InternalEvent::change_playlist_orderings(orderings))
+    }
+
+    /// The metadata currently published.
+    fn metadata(&self) -> OwnedMediaMetadata {
+        self.state.lock().unwrap().published_metadata()
+    }
+
+    /// The playback status currently published.
+    fn playback(&self) -> MediaPlayback {
+        self.state.lock().unwrap().current_playback()
+    }
+
+    /// The volume level currently published.
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
+
+    /// The actions currently published as supported.
+    fn capabilities(&self) -> Capabilities {
+        self.state.lock().unwrap().capabilities
+    }
+}
+
+type EventHandler = Arc<dyn Fn(MediaControlEvent) -> Result<(), EventError> + Send + Sync>;
+
+/// The error sent back when the event handler fails to carry out a method call.
+#[derive(Debug)]
+struct HandlerError {
+    name: &'static str,
+    description: String,
+}
+
+impl From<EventError> for HandlerError {
+    fn from(error: EventError) -> Self {
+        Self {
+            name: method_error_name(&error),
+            description: error.to_string(),
+        }
+    }
+}
+
+impl DBusError for HandlerError {
+    fn create_reply(&self, call: &MessageHeader<'_>) -> zbus::Result<zbus::Message> {
+        MessageBuilder::error(call, self.name())?.build(&(self.description.as_str(),))
+    }
+
+    fn name(&self) -> ErrorName<'_> {
+        ErrorName::from_static_str_unchecked(self.name)
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some(&self.description)
+    }
+}
+
+/// Sends the event of a method call to the handler.
+fn handle_method(
+    event_handler: &EventHandler,
+    event: MediaControlEvent,
+) -> Result<(), HandlerError> {
+    event_handler(event).map_err(HandlerError::from)
+}
+
+/// Sends the event of a property change to the handler.
+///
+/// The errors of property setters can't be given a name with zbus 3, so they are all sent
+/// as `org.freedesktop.zbus.Error`.
+fn handle_property(event_handler: &EventHandler, event: MediaControlEvent) -> zbus::Result<()> {
+    event_handler(event).map_err(|error| zbus::Error::Failure(error.to_string()))
+}
+
+/// The properties of an interface which clients can write.
+trait WritableProperties {
+    /// Writes `value` to `property`, or returns `None` if it isn't writable.
+    fn write(&self, property: &str, value: &Value<'_>) -> Option<zbus::Result<()>>;
+}
+
+/// Converts the value written to a property.
+fn written_value<'v, T>(value: &Value<'v>) -> zbus::Result<T>
+where
+    T: TryFrom<Value<'v>, Error = zvariant::Error>,
+{
+    T::try_from(value.clone()).map_err(zbus::Error::Variant)
+}
+
+/// Serves the interface `I`, without announcing the properties written by clients.
+///
+/// zbus 3 announces a property after each successful write, but a write only asks the
+/// application for a change. The property is announced along with the other changes of
+/// the state, once the application confirms it.
+struct ConfirmedWrites<I>(I);
+
+impl<I: Interface + WritableProperties> Interface for ConfirmedWrites<I> {
+    fn name() -> InterfaceName<'static> {
+        I::name()
+    }
+
+    fn get<'life0, 'life1, 'async_trait>(
+        &'life0 self,
+        property_name: &'life1 str,
+    ) -> Pin<Box<dyn Future<Output = Option<fdo::Result<OwnedValue>>> + Send + 'async_trait>>
+    where
+        'life0: 'async_trait,
+        'life1: 'async_trait,
+        Self: 'async_trait,
+    {
+        self.0.get(property_name)
+    }
+
+    fn get_all<'life0, 'async_trait>(
+        &'life0 self,
+    ) -> Pin<Box<dyn Future<Output = HashMap<String, OwnedValue>> + Send + 'async_trait>>
+    where
+        'life0: 'async_trait,
+        Self: 'async_trait,
+    {
+        self.0.get_all()
+    }
+
+    fn set<'call>(
+        &'call self,
+        property_name: &'call str,
+        value: &'call Value<'_>,
+        _: &'call SignalContext<'_>,
+    ) -> DispatchResult<'call> {
+        match self.0.write(property_name, value) {
+            Some(result) => DispatchResult::Async(Box::pin(future::ready(result))),
+            None => DispatchResult::NotFound,
+        }
+    }
+
+    fn set_mut<'life0, 'life1, 'life2, 'life3, 'life4, 'life5, 'async_trait>(
+        &'life0 mut self,
+        _: &'life1 str,
+        _: &'life2 Value<'life3>,
+        _: &'life4 SignalContext<'life5>,
+    ) -> Pin<Box<dyn Future<Output = Option<fdo::Result<()>>> + Send + 'async_trait>>
+    where
+        'life0: 'async_trait,
+        'life1: 'async_trait,
+        'life2: 'async_trait,
+        'life3: 'async_trait,
+        'life4: 'async_trait,
+        'life5: 'async_trait,
+        Self: 'async_trait,
+    {
+        // Every write is handled by `set`.
+        Box::pin(future::ready(None))
+    }
+
+    fn call<'call>(
+        &'call self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult<'call> {
+        self.0.call(server, connection, msg, name)
+    }
+
+    fn call_mut<'call>(
+        &'call mut self,
+        server: &'call ObjectServer,
+        connection: &'call Connection,
+        msg: &'call Message,
+        name: MemberName<'call>,
+    ) -> DispatchResult<'call> {
+        self.0.call_mut(server, connection, msg, name)
+    }
+
+    fn introspect_to_writer(&self, writer: &mut dyn Write, level: usize) {
+        self.0.introspect_to_writer(writer, level)
     }
 }
 
 struct AppInterface {
     friendly_name: String,
-    event_handler: Arc<Mutex<dyn Fn(MediaControlEvent) + Send + 'static>>,
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: EventHandler,
 }
 
 #[dbus_interface(name = "org.mpris.MediaPlayer2")]
 impl AppInterface {
-    fn raise(&self) {
-        self.send_event(MediaControlEvent::Raise);
+    fn raise(&self) -> Result<(), HandlerError> {
+        handle_method(&self.event_handler, MediaControlEvent::Raise)
     }
-    fn quit(&self) {
-        self.send_event(MediaControlEvent::Quit);
+    fn quit(&self) -> Result<(), HandlerError> {
+        handle_method(&self.event_handler, MediaControlEvent::Quit)
     }
 
     #[dbus_interface(property)]
     fn can_quit(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_quit
     }
 
     #[dbus_interface(property)]
     fn can_raise(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_raise
+    }
+
+    #[dbus_interface(property)]
+    fn can_set_fullscreen(&self) -> bool {
+        self.state.lock().unwrap().capabilities.can_set_fullscreen
+    }
+
+    #[dbus_interface(property)]
+    fn fullscreen(&self) -> bool {
+        self.state.lock().unwrap().fullscreen
+    }
+
+    #[dbus_interface(property)]
+    fn set_fullscreen(&self, fullscreen: bool) -> zbus::Result<()> {
+        handle_property(
+            &self.event_handler,
+            MediaControlEvent::SetFullscreen(fullscreen),
+        )
     }
 
     #[dbus_interface(property)]
     fn has_tracklist(&self) -> bool {
-        false
+        self.state.lock().unwrap().tracklist.is_some()
     }
 
     #[dbus_interface(property)]
@@ -176,268 +644,688 @@ impl AppInterface {
     }
 
     #[dbus_interface(property)]
-    fn supported_uri_schemes(&self) -> &[&str] {
-        &[]
+    fn desktop_entry(&self) -> String {
+        self.state
+            .lock()
+            .unwrap()
+            .root_properties
+            .desktop_entry
+            .clone()
     }
 
     #[dbus_interface(property)]
-    fn supported_mime_types(&self) -> &[&str] {
-        &[]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.root_properties.supported_uri_schemes.clone()
+    }
+
+    #[dbus_interface(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        let state = self.state.lock().unwrap();
+        state.root_properties.supported_mime_types.clone()
     }
 }
 
-impl AppInterface {
-    fn send_event(&self, event: MediaControlEvent) {
-        (self.event_handler.lock().unwrap())(event);
+impl WritableProperties for AppInterface {
+    fn write(&self, property: &str, value: &Value<'_>) -> Option<zbus::Result<()>> {
+        match property {
+            "Fullscreen" => Some(written_value(value).and_then(|v| self.set_fullscreen(v))),
+            _ => None,
+        }
     }
 }
 
 struct PlayerInterface {
-    state: ServiceState,
-    event_handler: Arc<Mutex<dyn Fn(MediaControlEvent) + Send + 'static>>,
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: EventHandler,
+}
+
+impl WritableProperties for PlayerInterface {
+    fn write(&self, property: &str, value: &Value<'_>) -> Option<zbus::Result<()>> {
+        Some(match property {
+            "Rate" => written_value(value).and_then(|v| self.set_rate(v)),
+            "Volume" => written_value(value).and_then(|v| self.set_volume(v)),
+            "Shuffle" => written_value(value).and_then(|v| self.set_shuffle(v)),
+            "LoopStatus" => written_value(value).and_then(|v| self.set_loop_status(v)),
+            _ => return None,
+        })
+    }
 }
 
 impl PlayerInterface {
-    fn send_event(&self, event: MediaControlEvent) {
-        (self.event_handler.lock().unwrap())(event);
+    fn send_event(&self, event: MediaControlEvent) -> Result<(), HandlerError> {
+        handle_method(&self.event_handler, event)
+    }
+
+    fn set_property(&self, event: MediaControlEvent) -> zbus::Result<()> {
+        handle_property(&self.event_handler, event)
     }
 }
 
 #[dbus_interface(name = "org.mpris.MediaPlayer2.Player")]
 impl PlayerInterface {
-    fn next(&self) {
-        self.send_event(MediaControlEvent::Next);
+    fn next(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Next)
     }
-    fn previous(&self) {
-        self.send_event(MediaControlEvent::Previous);
+    fn previous(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Previous)
     }
-    fn pause(&self) {
-        self.send_event(MediaControlEvent::Pause);
+    fn pause(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Pause)
     }
-    fn play_pause(&self) {
-        self.send_event(MediaControlEvent::Toggle);
+    fn play_pause(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Toggle)
     }
-    fn stop(&self) {
-        self.send_event(MediaControlEvent::Stop);
+    fn stop(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Stop)
     }
-    fn play(&self) {
-        self.send_event(MediaControlEvent::Play);
+    fn play(&self) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::Play)
     }
 
-    fn seek(&self, offset: i64) {
-        let abs_offset = offset.unsigned_abs();
-        let direction = if offset > 0 {
-            SeekDirection::Forward
-        } else {
-            SeekDirection::Backward
-        };
-
-        self.send_event(MediaControlEvent::SeekBy(
-            direction,
-            Duration::from_micros(abs_offset),
-        ));
-
-        // NOTE: Should the `Seeked` signal be called when calling this method?
+    fn seek(&self, offset: i64) -> Result<(), HandlerError> {
+        self.send_event(seek_event(offset))
     }
 
-    fn set_position(&self, _track_id: zvariant::ObjectPath, position: i64) {
-        if let Ok(micros) = position.try_into() {
-            if let Some(duration) = self.state.metadata.duration {
-                // If the Position argument is greater than the track length, do nothing.
-                if position > duration {
-                    return;
-                }
-            }
-
-            let position = Duration::from_micros(micros);
-            self.send_event(MediaControlEvent::SetPosition(MediaPosition(position)));
+    fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> Result<(), HandlerError> {
+        let event = self
+            .state
+            .lock()
+            .unwrap()
+            .set_position_event(track_id.as_str(), position);
+        match event {
+            Some(event) => self.send_event(event),
+            None => Ok(()),
         }
     }
 
-    fn open_uri(&self, uri: String) {
-        // NOTE: we should check if the URI is in the `SupportedUriSchemes` list.
-        self.send_event(MediaControlEvent::OpenUri(uri));
+    #[dbus_interface(signal)]
+    async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    fn open_uri(&self, uri: String) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::OpenUri(uri))
     }
 
     #[dbus_interface(property)]
     fn playback_status(&self) -> &'static str {
-        match self.state.playback_status {
-            MediaPlayback::Playing { .. } => "Playing",
-            MediaPlayback::Paused { .. } => "Paused",
-            MediaPlayback::Stopped => "Stopped",
-        }
+        self.state.lock().unwrap().playback_status_name()
     }
 
     #[dbus_interface(property)]
     fn rate(&self) -> f64 {
-        1.0
+        self.state.lock().unwrap().rate
     }
 
     #[dbus_interface(property)]
-    fn metadata(&self) -> HashMap<&str, Value> {
-        // TODO: this should be stored in a cache inside the state.
-        let mut dict = HashMap::<&str, Value>::new();
+    fn set_rate(&self, rate: f64) -> zbus::Result<()> {
+        let event = self.state.lock().unwrap().rate_event(rate);
+        match event {
+            Some(event) => self.set_property(event),
+            None => Ok(()),
+        }
+    }
 
-        let OwnedMetadata {
-            ref title,
-            ref album,
-            ref artist,
-            ref cover_url,
-            ref duration,
-        } = self.state.metadata;
+    #[dbus_interface(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        metadata_dict(self.state.lock().unwrap().metadata_dict.clone())
+    }
 
-        // MPRIS
-        dict.insert(
-            "mpris:trackid",
-            // TODO: this is just a workaround to enable SetPosition.
-            Value::new(ObjectPath::try_from("/").unwrap()),
-        );
+    #[dbus_interface(property)]
+    fn volume(&self) -> f64 {
+        self.state.lock().unwrap().volume
+    }
 
-        if let Some(length) = duration {
-            dict.insert("mpris:length", Value::new(*length));
-        }
+    #[dbus_interface(property)]
+    fn set_volume(&self, volume: f64) -> zbus::Result<()> {
+        self.set_property(MediaControlEvent::SetVolume(volume))
+    }
 
-        if let Some(cover_url) = cover_url {
-            dict.insert("mpris:artUrl", Value::new(cover_url.clone()));
-        }
+    #[dbus_interface(property)]
+    fn shuffle(&self) -> bool {
+        self.state.lock().unwrap().shuffle
+    }
 
-        // Xesam
-        if let Some(title) = title {
-            dict.insert("xesam:title", Value::new(title.clone()));
-        }
-        if let Some(artist) = artist {
-            dict.insert("xesam:artist", Value::new(vec![artist.clone()]));
-        }
-        if let Some(album) = album {
-            dict.insert("xesam:album", Value::new(album.clone()));
-        }
-        dict
+    #[dbus_interface(property)]
+    fn set_shuffle(&self, shuffle: bool) -> zbus::Result<()> {
+        self.set_property(MediaControlEvent::SetShuffle(shuffle))
     }
 
     #[dbus_interface(property)]
-    fn volume(&self) -> f64 {
-        self.state.volume
+    fn loop_status(&self) -> &'static str {
+        loop_status_name(self.state.lock().unwrap().loop_status)
     }
 
     #[dbus_interface(property)]
-    fn set_volume(&self, volume: f64) {
-        self.send_event(MediaControlEvent::SetVolume(volume));
+    fn set_loop_status(&self, loop_status: String) -> zbus::Result<()> {
+        let loop_status =
+            parse_loop_status(&loop_status).ok_or_else(|| fdo::Error::InvalidArgs(loop_status))?;
+        self.set_property(MediaControlEvent::SetLoopStatus(loop_status))
     }
 
     #[dbus_interface(property)]
     fn position(&self) -> i64 {
-        let position = match self.state.playback_status {
-            MediaPlayback::Playing {
-                progress: Some(pos),
-            }
-            | MediaPlayback::Paused {
-                progress: Some(pos),
-            } => pos.0.as_micros(),
-            _ => 0,
-        };
-
-        position.try_into().unwrap_or(0)
+        self.state.lock().unwrap().position()
     }
 
     #[dbus_interface(property)]
     fn minimum_rate(&self) -> f64 {
-        1.0
+        self.state.lock().unwrap().minimum_rate
     }
 
     #[dbus_interface(property)]
     fn maximum_rate(&self) -> f64 {
-        1.0
+        self.state.lock().unwrap().maximum_rate
     }
 
     #[dbus_interface(property)]
     fn can_go_next(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_go_next
     }
 
     #[dbus_interface(property)]
     fn can_go_previous(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_go_previous
     }
 
     #[dbus_interface(property)]
     fn can_play(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_play
     }
 
     #[dbus_interface(property)]
     fn can_pause(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_pause
     }
 
     #[dbus_interface(property)]
     fn can_seek(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_seek
     }
 
     #[dbus_interface(property)]
     fn can_control(&self) -> bool {
-        true
+        self.state.lock().unwrap().capabilities.can_control
     }
 }
 
+struct TrackListInterface {
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: EventHandler,
+}
+
+impl TrackListInterface {
+    fn send_event(&self, event: MediaControlEvent) -> Result<(), HandlerError> {
+        handle_method(&self.event_handler, event)
+    }
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2.TrackList")]
+impl TrackListInterface {
+    fn get_tracks_metadata(
+        &self,
+        track_ids: Vec<ObjectPath<'_>>,
+    ) -> Vec<HashMap<String, Value<'static>>> {
+        let state = self.state.lock().unwrap();
+        track_ids
+            .iter()
+            .filter_map(|id| {
+                let metadata = state.get_track(id)?;
+                Some(metadata_dict(create_metadata_dict(id, metadata)))
+            })
+            .collect()
+    }
+
+    fn add_track(
+        &self,
+        uri: String,
+        after_track: ObjectPath<'_>,
+        set_as_current: bool,
+    ) -> Result<(), HandlerError> {
+        self.send_event(add_track_event(uri, &after_track, set_as_current))
+    }
+
+    fn remove_track(&self, track_id: ObjectPath<'_>) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::RemoveTrack(TrackId(
+            track_id.to_string(),
+        )))
+    }
+
+    fn go_to(&self, track_id: ObjectPath<'_>) -> Result<(), HandlerError> {
+        self.send_event(MediaControlEvent::GoTo(TrackId(track_id.to_string())))
+    }
+
+    #[dbus_interface(signal)]
+    async fn track_list_replaced(
+        ctxt: &SignalContext<'_>,
+        tracks: Vec<ObjectPath<'_>>,
+        current_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn track_added(
+        ctxt: &SignalContext<'_>,
+        metadata: HashMap<String, Value<'_>>,
+        after_track: ObjectPath<'_>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn track_removed(ctxt: &SignalContext<'_>, track_id: ObjectPath<'_>) -> zbus::Result<()>;
+
+    #[dbus_interface(signal)]
+    async fn track_metadata_changed(
+        ctxt: &SignalContext<'_>,
+        track_id: ObjectPath<'_>,
+        metadata: HashMap<String, Value<'_>>,
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(property)]
+    fn tracks(&self) -> Vec<ObjectPath<'static>> {
+        let track_ids = self.state.lock().unwrap().track_ids();
+        track_ids.into_iter().map(track_path).collect()
+    }
+
+    #[dbus_interface(property)]
+    fn can_edit_tracks(&self) -> bool {
+        self.state.lock().unwrap().capabilities.can_edit_tracks
+    }
+}
+
+struct PlaylistsInterface {
+    state: Arc<Mutex<ServiceState>>,
+    event_handler: EventHandler,
+}
+
+#[dbus_interface(name = "org.mpris.MediaPlayer2.Playlists")]
+impl PlaylistsInterface {
+    fn activate_playlist(&self, playlist_id: ObjectPath<'_>) -> Result<(), HandlerError> {
+        handle_method(
+            &self.event_handler,
+            MediaControlEvent::ActivatePlaylist(PlaylistId(playlist_id.to_string())),
+        )
+    }
+
+    fn get_playlists(
+        &self,
+        index: u32,
+        max_count: u32,
+        order: String,
+        reverse_order: bool,
+    ) -> Vec<(ObjectPath<'static>, String, String)> {
+        let state = self.state.lock().unwrap();
+        get_playlists(&state.playlists, index, max_count, &order, reverse_order)
+            .into_iter()
+            .map(playlist_struct)
+            .collect()
+    }
+
+    #[dbus_interface(signal)]
+    async fn playlist_changed(
+        ctxt: &SignalContext<'_>,
+        playlist: (ObjectPath<'_>, String, String),
+    ) -> zbus::Result<()>;
+
+    #[dbus_interface(property)]
+    fn playlist_count(&self) -> u32 {
+        self.state.lock().unwrap().playlists.len() as u32
+    }
+
+    #[dbus_interface(property)]
+    fn orderings(&self) -> Vec<String> {
+        self.state.lock().unwrap().playlist_ordering_names()
+    }
+
+    #[dbus_interface(property)]
+    fn active_playlist(&self) -> (bool, (ObjectPath<'static>, String, String)) {
+        active_playlist_struct(self.state.lock().unwrap().get_active_playlist())
+    }
+}
+
+/// Converts a playlist to the `(oss)` struct used by the MPRIS Playlists interface.
+fn playlist_struct(playlist: &OwnedPlaylist) -> (ObjectPath<'static>, String, String) {
+    (
+        // Playlist ids are already validated by `MediaControls`.
+        ObjectPath::from_string_unchecked(playlist.id.clone()),
+        playlist.name.clone(),
+        playlist.icon.clone(),
+    )
+}
+
+/// Converts the active playlist to the `(b(oss))` struct of the `ActivePlaylist` property.
+fn active_playlist_struct(
+    playlist: Option<&OwnedPlaylist>,
+) -> (bool, (ObjectPath<'static>, String, String)) {
+    match playlist {
+        Some(playlist) => (true, playlist_struct(playlist)),
+        None => (
+            false,
+            (
+                ObjectPath::from_static_str_unchecked("/"),
+                String::new(),
+                String::new(),
+            ),
+        ),
+    }
+}
+
+/// Converts a track id to an object path.
+///
+/// Track ids are already validated by `MediaControls` before reaching the service.
+fn track_path(id: String) -> ObjectPath<'static> {
+    ObjectPath::from_string_unchecked(id)
+}
+
+/// Converts a value of the service state to a value which can be sent by `zbus`.
+fn to_value(value: MprisValue) -> Value<'static> {
+    match value {
+        MprisValue::Bool(value) => Value::new(value),
+        MprisValue::Int32(value) => Value::new(value),
+        MprisValue::Int64(value) => Value::new(value),
+        MprisValue::UInt32(value) => Value::new(value),
+        MprisValue::Double(value) => Value::new(value),
+        MprisValue::String(value) => Value::new(value),
+        MprisValue::ObjectPath(path) => Value::new(track_path(path)),
+        MprisValue::StringList(list) => Value::new(list),
+        MprisValue::ActivePlaylist(playlist) => {
+            Value::from(active_playlist_struct(playlist.as_ref()))
+        }
+        MprisValue::Metadata(dict) => Value::from(metadata_dict(dict)),
+    }
+}
+
+fn metadata_dict(dict: MetadataDict) -> HashMap<String, Value<'static>> {
+    dict.into_iter()
+        .map(|(key, value)| (key, to_value(value)))
+        .collect()
+}
+
+async fn emit_signal(ctxt: &SignalContext<'_>, signal: Signal) -> zbus::Result<()> {
+    match signal {
+        Signal::Seeked(position) => PlayerInterface::seeked(ctxt, position).await,
+        Signal::TrackListReplaced { tracks, current } => {
+            let tracks = tracks.into_iter().map(track_path).collect();
+            TrackListInterface::track_list_replaced(ctxt, tracks, track_path(current)).await
+        }
+        Signal::TrackAdded { metadata, after } => {
+            TrackListInterface::track_added(ctxt, metadata_dict(metadata), track_path(after)).await
+        }
+        Signal::TrackRemoved(id) => TrackListInterface::track_removed(ctxt, track_path(id)).await,
+        Signal::TrackMetadataChanged { id, metadata } => {
+            TrackListInterface::track_metadata_changed(
+                ctxt,
+                track_path(id),
+                metadata_dict(metadata),
+            )
+            .await
+        }
+        Signal::PlaylistChanged(playlist) => {
+            PlaylistsInterface::playlist_changed(ctxt, playlist_struct(&playlist)).await
+        }
+    }
+}
+
+/// Publishes the updates sent by the application, and reports the changes of ownership of
+/// the bus name, until the controls are detached. Returns an error if the connection is
+/// lost.
 async fn run_service(
-    dbus_name: String,
-    friendly_name: String,
-    event_handler: Arc<Mutex<dyn Fn(MediaControlEvent) + Send + 'static>>,
-    event_channel: mpsc::Receiver<InternalEvent>,
+    connection: &Connection,
+    bus_name: &str,
+    event_channel: &async_channel::Receiver<Notifications>,
+    name_changes: NameChanges,
+    internal_executor: bool,
 ) -> zbus::Result<()> {
+    let updates = Box::pin(async {
+        // The channel is closed when the controls are detached or dropped.
+        while let Ok(notifications) = event_channel.recv().await {
+            emit(connection, notifications).await?;
+        }
+        Ok::<_, zbus::Error>(())
+    });
+    let updates = async {
+        match future::select(updates, name_changes).await {
+            Either::Left((result, _)) => result?,
+            // The name changes are watched until the connection is closed.
+            Either::Right(((), _)) => {
+                let err = io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed");
+                return Err(err.into());
+            }
+        }
+        // The name changes aren't watched anymore, so releasing the name isn't reported as
+        // a lost name.
+        disconnect(connection, bus_name).await
+    };
+
+    if internal_executor {
+        updates.await
+    } else {
+        run_with_executor(connection, updates).await
+    }
+}
+
+/// Runs the tasks of the connection, which handle the incoming method calls, until
+/// `future` completes. Only needed if the connection has no internal executor thread.
+pub(super) async fn run_with_executor<T>(
+    connection: &Connection,
+    future: impl Future<Output = T>,
+) -> T {
+    let executor = connection.executor();
+    let mut future = Box::pin(future);
+
+    loop {
+        let mut tick = Box::pin(executor.tick());
+        let output = poll_fn(|cx| match future.as_mut().poll(cx) {
+            Poll::Ready(output) => Poll::Ready(Some(output)),
+            Poll::Pending => tick.as_mut().poll(cx).map(|()| None),
+        })
+        .await;
+
+        if let Some(output) = output {
+            return output;
+        }
+    }
+}
+
+/// Serves the MPRIS interfaces on the connection given by `dbus_connection`, and requests
+/// `bus_name`.
+///
+/// Also returns a future which reports the changes of ownership of the name, see
+/// [`watch_name`].
+pub(super) async fn connect(
+    dbus_connection: &DbusConnection,
+    bus_name: &str,
+    friendly_name: String,
+    state: &Arc<Mutex<ServiceState>>,
+    event_handler: SharedEventHandler,
+    internal_executor: bool,
+) -> Result<(Connection, NameChanges), Error> {
+    let connection = open(dbus_connection, internal_executor).await?;
+    let setup = async {
+        serve(&connection, friendly_name, state, event_handler.clone()).await?;
+        let name_changes = async {
+            // The name is watched before it is requested, so that no change is missed.
+            let name_changes = watch_name(&connection, bus_name, event_handler).await?;
+            request_name(&connection, bus_name).await?;
+            Ok::<_, Error>(name_changes)
+        }
+        .await;
+        if name_changes.is_err() {
+            // The connection may be shared with the application, which keeps it open.
+            remove_interfaces(&connection).await.ok();
+        }
+        name_changes
+    };
+    // The replies are only received while the executor runs.
+    let name_changes = if internal_executor {
+        setup.await?
+    } else {
+        run_with_executor(&connection, setup).await?
+    };
+    Ok((connection, name_changes))
+}
+
+/// Opens the connection given by `dbus_connection`, or clones the one of the application.
+async fn open(
+    dbus_connection: &DbusConnection,
+    internal_executor: bool,
+) -> zbus::Result<Connection> {
+    let builder = match dbus_connection {
+        DbusConnection::Session => ConnectionBuilder::session()?,
+        DbusConnection::System => ConnectionBuilder::system()?,
+        DbusConnection::Address(address) => ConnectionBuilder::address(address.as_str())?,
+        DbusConnection::Zbus(connection) => return Ok(connection.clone()),
+    };
+    builder.internal_executor(internal_executor).build().await
+}
+
+/// Serves the MPRIS interfaces on `connection`.
+async fn serve(
+    connection: &Connection,
+    friendly_name: String,
+    state: &Arc<Mutex<ServiceState>>,
+    event_handler: SharedEventHandler,
+) -> Result<(), Error> {
+    let event_handler: EventHandler = Arc::new(filter_events(state, event_handler));
+
     let app = AppInterface {
         friendly_name,
+        state: state.clone(),
         event_handler: event_handler.clone(),
     };
 
     let player = PlayerInterface {
-        state: ServiceState {
-            metadata: OwnedMetadata::default(),
-            playback_status: MediaPlayback::Stopped,
-            volume: 1.0,
-        },
+        state: state.clone(),
+        event_handler: event_handler.clone(),
+    };
+
+    let tracklist = TrackListInterface {
+        state: state.clone(),
+        event_handler: event_handler.clone(),
+    };
+
+    let playlists = PlaylistsInterface {
+        state: state.clone(),
         event_handler,
     };
 
-    let name = format!("org.mpris.MediaPlayer2.{dbus_name}");
-    let path = ObjectPath::try_from("/org/mpris/MediaPlayer2")?;
-    let connection = ConnectionBuilder::session()?
-        .serve_at(&path, app)?
-        .serve_at(&path, player)?
-        .name(name.as_str())?
-        .build()
+    let server = connection.object_server();
+    // Other media controls may already be served on a connection shared with the
+    // application. They are left as they are.
+    if !server.at(OBJECT_PATH, ConfirmedWrites(app)).await? {
+        return Err(Error::AlreadyServed);
+    }
+    server.at(OBJECT_PATH, ConfirmedWrites(player)).await?;
+    server.at(OBJECT_PATH, tracklist).await?;
+    server.at(OBJECT_PATH, playlists).await?;
+    Ok(())
+}
+
+/// Stops publishing the media controls on `connection`, by removing the MPRIS interfaces
+/// and releasing `bus_name`.
+///
+/// The connection may be shared with the application, which keeps it open. zbus also keeps
+/// its own connections open while it watches whether the name is lost.
+pub(super) async fn disconnect(connection: &Connection, bus_name: &str) -> zbus::Result<()> {
+    remove_interfaces(connection).await?;
+    connection.release_name(bus_name).await?;
+    Ok(())
+}
+
+async fn remove_interfaces(connection: &Connection) -> zbus::Result<()> {
+    let server = connection.object_server();
+    server
+        .remove::<ConfirmedWrites<AppInterface>, _>(OBJECT_PATH)
+        .await?;
+    server
+        .remove::<ConfirmedWrites<PlayerInterface>, _>(OBJECT_PATH)
         .await?;
+    server.remove::<TrackListInterface, _>(OBJECT_PATH).await?;
+    server.remove::<PlaylistsInterface, _>(OBJECT_PATH).await?;
+    Ok(())
+}
 
-    loop {
-        if let Ok(event) = event_channel.recv_timeout(Duration::from_millis(10)) {
-            if event == InternalEvent::Kill {
-                break;
-            }
+/// Requests `name` on the bus, letting another application take it over later.
+///
+/// The name isn't taken from an application which already owns it.
+async fn request_name(connection: &Connection, name: &str) -> Result<(), Error> {
+    let flags = RequestNameFlags::AllowReplacement.into();
+    match connection.request_name_with_flags(name, flags).await? {
+        RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner => Ok(()),
+        RequestNameReply::InQueue | RequestNameReply::Exists => {
+            connection.release_name(name).await?;
+            Err(Error::NameTaken(name.to_owned()))
+        }
+    }
+}
 
-            let interface_ref = connection
-                .object_server()
-                .interface::<_, PlayerInterface>(&path)
-                .await?;
-            let mut interface = interface_ref.get_mut().await;
-            let ctxt = SignalContext::new(&connection, &path)?;
-
-            match event {
-                InternalEvent::ChangeMetadata(metadata) => {
-                    interface.state.metadata = metadata;
-                    interface.metadata_changed(&ctxt).await?;
-                }
-                InternalEvent::ChangePlayback(playback) => {
-                    interface.state.playback_status = playback;
-                    interface.playback_status_changed(&ctxt).await?;
-                }
-                InternalEvent::ChangeVolume(volume) => {
-                    interface.state.volume = volume;
-                    interface.volume_changed(&ctxt).await?;
-                }
-                InternalEvent::Kill => (),
+/// Reports the changes of ownership of the bus name, see [`watch_name`].
+pub(super) type NameChanges = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// Watches the ownership of `bus_name`. The returned future sends `NameLost` and
+/// `NameAcquired` to the event handler when another application takes the name, and when
+/// it is given back. It only completes if the connection is closed.
+async fn watch_name(
+    connection: &Connection,
+    bus_name: &str,
+    event_handler: SharedEventHandler,
+) -> zbus::Result<NameChanges> {
+    let proxy = fdo::DBusProxy::new(connection).await?;
+    let lost = proxy.receive_name_lost().await?.map(|signal| {
+        let name = signal.args().map(|args| args.name.to_string());
+        (false, name.ok())
+    });
+    let acquired = proxy.receive_name_acquired().await?.map(|signal| {
+        let name = signal.args().map(|args| args.name.to_string());
+        (true, name.ok())
+    });
+    let mut changes = Box::pin(stream::select(lost, acquired));
+
+    let bus_name = bus_name.to_owned();
+    Ok(Box::pin(async move {
+        let mut owns_name = true;
+        while let Some((owns, name)) = changes.next().await {
+            if name.as_deref() != Some(bus_name.as_str()) || owns == owns_name {
+                continue;
             }
+            owns_name = owns;
+            let event = if owns {
+                MediaControlEvent::NameAcquired
+            } else {
+                MediaControlEvent::NameLost
+            };
+            notify(&event_handler, event);
         }
+    }))
+}
+
+/// Notifies the clients of the changes made to the state.
+pub(super) async fn emit(
+    connection: &Connection,
+    notifications: Notifications,
+) -> zbus::Result<()> {
+    let Notifications {
+        signals,
+        properties_changed,
+    } = notifications;
+
+    let ctxt = SignalContext::new(connection, OBJECT_PATH)?;
+    for signal in signals {
+        emit_signal(&ctxt, signal).await?;
+    }
+    for properties in properties_changed {
+        let changed: Vec<(&str, Value)> = properties
+            .changed
+            .into_iter()
+            .map(|(name, value)| (name, to_value(value)))
+            .collect();
+        let changed: HashMap<&str, &Value> =
+            changed.iter().map(|(name, value)| (*name, value)).collect();
+
+        fdo::Properties::properties_changed(
+            &ctxt,
+            InterfaceName::from_static_str_unchecked(properties.interface),
+            &changed,
+            &properties.invalidated,
+        )
+        .await?;
     }
 
     Ok(())
@@ -0,0 +1,65 @@
+use crate::{
+    BoxedEventHandler, Capabilities, Error, LoopStatus, MediaControlsBackend, MediaPlayback,
+    OwnedMediaMetadata, PlatformConfig,
+};
+
+/// A backend which does nothing, available on every platform.
+///
+/// It can be used as a fallback when the OS media controls are unavailable, e.g. when
+/// there's no D-Bus session bus. Since nothing is published, the getters always return
+/// the default state.
+#[derive(Debug, Default)]
+pub struct NoopBackend;
+
+impl NoopBackend {
+    /// Create media controls with the specified config.
+    pub fn new(_config: PlatformConfig) -> Result<Self, Error> {
+        Ok(Self)
+    }
+}
+
+impl MediaControlsBackend for NoopBackend {
+    fn attach(&mut self, _event_handler: BoxedEventHandler) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn detach(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_playback(&mut self, _playback: MediaPlayback) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_metadata(&mut self, _metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_shuffle(&mut self, _shuffle: bool) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_loop_status(&mut self, _loop_status: LoopStatus) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_rate(&mut self, _rate: f64) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn set_capabilities(&mut self, _capabilities: Capabilities) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn metadata(&self) -> OwnedMediaMetadata {
+        OwnedMediaMetadata::default()
+    }
+
+    fn playback(&self) -> MediaPlayback {
+        MediaPlayback::Stopped
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
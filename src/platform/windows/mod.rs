@@ -1,5 +1,6 @@
 #![cfg(target_os = "windows")]
 
+use std::convert::TryFrom;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use windows::core::{Error as WindowsError, HSTRING};
@@ -10,17 +11,27 @@ use windows::Win32::Foundation::HWND;
 use windows::Win32::System::WinRT::ISystemMediaTransportControlsInterop;
 
 use crate::{
-    MediaControlEvent, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig, SeekDirection,
+    BoxedEventHandler, Capabilities, LoopStatus, MediaControlEvent, MediaControlsBackend,
+    MediaPlayback, MediaPosition, OwnedMediaMetadata, PlatformConfig, SeekDirection,
 };
 
-/// A handle to OS media controls.
-pub struct MediaControls {
+/// The backend built on the System Media Transport Controls of Windows.
+pub struct WindowsBackend {
     controls: SystemMediaTransportControls,
     button_handler_token: Option<EventRegistrationToken>,
+    shuffle_handler_token: Option<EventRegistrationToken>,
+    repeat_handler_token: Option<EventRegistrationToken>,
+    rate_handler_token: Option<EventRegistrationToken>,
     display_updater: SystemMediaTransportControlsDisplayUpdater,
     timeline_properties: SystemMediaTransportControlsTimelineProperties,
+    capabilities: Capabilities,
+    metadata: OwnedMediaMetadata,
+    playback: MediaPlayback,
 }
 
+/// The backend used by [`MediaControls::new`](crate::MediaControls::new).
+pub(crate) type DefaultBackend = WindowsBackend;
+
 #[repr(i32)]
 #[derive(Copy, Clone, PartialEq, Eq, Debug)]
 enum SmtcPlayback {
@@ -47,7 +58,7 @@ impl From<WindowsError> for Error {
     }
 }
 
-impl MediaControls {
+impl WindowsBackend {
     /// Create media controls with the specified config.
     pub fn new(config: PlatformConfig) -> Result<Self, Error> {
         let interop: ISystemMediaTransportControlsInterop = windows::core::factory::<
@@ -68,26 +79,27 @@ impl MediaControls {
             display_updater,
             timeline_properties,
             button_handler_token: None,
+            shuffle_handler_token: None,
+            repeat_handler_token: None,
+            rate_handler_token: None,
+            capabilities: config.capabilities,
+            metadata: OwnedMediaMetadata::default(),
+            playback: MediaPlayback::Stopped,
         })
     }
+}
 
+impl MediaControlsBackend for WindowsBackend {
     /// Attach the media control events to a handler.
-    pub fn attach<F>(&mut self, event_handler: F) -> Result<(), Error>
-    where
-        F: Fn(MediaControlEvent) + Send + 'static,
-    {
+    fn attach(&mut self, event_handler: BoxedEventHandler) -> Result<(), Error> {
         self.controls.SetIsEnabled(true)?;
-        self.controls.SetIsPlayEnabled(true)?;
-        self.controls.SetIsPauseEnabled(true)?;
-        self.controls.SetIsStopEnabled(true)?;
-        self.controls.SetIsNextEnabled(true)?;
-        self.controls.SetIsPreviousEnabled(true)?;
-        self.controls.SetIsFastForwardEnabled(true)?;
-        self.controls.SetIsRewindEnabled(true)?;
+        self.set_capabilities(self.capabilities)?;
 
         // TODO: allow changing this
         self.display_updater.SetType(MediaPlaybackType::Music)?;
 
+        // Windows can't be told that an event failed, so the results of the handler are
+        // ignored.
         let event_handler = Arc::new(Mutex::new(event_handler));
 
         let button_handler = TypedEventHandler::new({
@@ -117,40 +129,106 @@ impl MediaControls {
                     return Ok(());
                 };
 
-                (event_handler.lock().unwrap())(event);
+                (event_handler.lock().unwrap())(event).ok();
                 Ok(())
             }
         });
         self.button_handler_token = Some(self.controls.ButtonPressed(&button_handler)?);
 
         let position_handler = TypedEventHandler::new({
+            let event_handler = event_handler.clone();
+
             move |_, args: &Option<_>| {
                 let args: &PlaybackPositionChangeRequestedEventArgs = args.as_ref().unwrap();
                 let position = Duration::from(args.RequestedPlaybackPosition()?);
 
                 (event_handler.lock().unwrap())(MediaControlEvent::SetPosition(MediaPosition(
                     position,
-                )));
+                )))
+                .ok();
                 Ok(())
             }
         });
         self.controls
             .PlaybackPositionChangeRequested(&position_handler)?;
 
+        let shuffle_handler = TypedEventHandler::new({
+            let event_handler = event_handler.clone();
+
+            move |_, args: &Option<_>| {
+                let args: &ShuffleEnabledChangeRequestedEventArgs = args.as_ref().unwrap();
+                let shuffle = args.RequestedShuffleEnabled()?;
+
+                (event_handler.lock().unwrap())(MediaControlEvent::SetShuffle(shuffle)).ok();
+                Ok(())
+            }
+        });
+        self.shuffle_handler_token = Some(
+            self.controls
+                .ShuffleEnabledChangeRequested(&shuffle_handler)?,
+        );
+
+        let repeat_handler = TypedEventHandler::new({
+            let event_handler = event_handler.clone();
+
+            move |_, args: &Option<_>| {
+                let args: &AutoRepeatModeChangeRequestedEventArgs = args.as_ref().unwrap();
+                let mode = args.RequestedAutoRepeatMode()?;
+
+                let loop_status = if mode == MediaPlaybackAutoRepeatMode::Track {
+                    LoopStatus::Track
+                } else if mode == MediaPlaybackAutoRepeatMode::List {
+                    LoopStatus::Playlist
+                } else {
+                    LoopStatus::None
+                };
+
+                (event_handler.lock().unwrap())(MediaControlEvent::SetLoopStatus(loop_status)).ok();
+                Ok(())
+            }
+        });
+        self.repeat_handler_token = Some(
+            self.controls
+                .AutoRepeatModeChangeRequested(&repeat_handler)?,
+        );
+
+        let rate_handler = TypedEventHandler::new({
+            move |_, args: &Option<_>| {
+                let args: &PlaybackRateChangeRequestedEventArgs = args.as_ref().unwrap();
+                let rate = args.RequestedPlaybackRate()?;
+
+                (event_handler.lock().unwrap())(MediaControlEvent::SetRate(rate)).ok();
+                Ok(())
+            }
+        });
+        self.rate_handler_token = Some(self.controls.PlaybackRateChangeRequested(&rate_handler)?);
+
         Ok(())
     }
 
     /// Detach the event handler.
-    pub fn detach(&mut self) -> Result<(), Error> {
+    fn detach(&mut self) -> Result<(), Error> {
         self.controls.SetIsEnabled(false)?;
         if let Some(button_handler_token) = self.button_handler_token {
             self.controls.RemoveButtonPressed(button_handler_token)?;
         }
+        if let Some(shuffle_handler_token) = self.shuffle_handler_token.take() {
+            self.controls
+                .RemoveShuffleEnabledChangeRequested(shuffle_handler_token)?;
+        }
+        if let Some(repeat_handler_token) = self.repeat_handler_token.take() {
+            self.controls
+                .RemoveAutoRepeatModeChangeRequested(repeat_handler_token)?;
+        }
+        if let Some(rate_handler_token) = self.rate_handler_token.take() {
+            self.controls
+                .RemovePlaybackRateChangeRequested(rate_handler_token)?;
+        }
         Ok(())
     }
 
     /// Set the current playback status.
-    pub fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
+    fn set_playback(&mut self, playback: MediaPlayback) -> Result<(), Error> {
         let status = match playback {
             MediaPlayback::Playing { .. } => SmtcPlayback::Playing as i32,
             MediaPlayback::Paused { .. } => SmtcPlayback::Paused as i32,
@@ -172,23 +250,40 @@ impl MediaControls {
 
         self.controls
             .UpdateTimelineProperties(&self.timeline_properties)?;
+        self.playback = playback;
         Ok(())
     }
 
     /// Set the metadata of the currently playing media item.
-    pub fn set_metadata(&mut self, metadata: MediaMetadata) -> Result<(), Error> {
+    fn set_metadata(&mut self, metadata: OwnedMediaMetadata) -> Result<(), Error> {
+        // Start from scratch, so that the fields which aren't set are removed.
+        self.display_updater.ClearAll()?;
+        self.display_updater.SetType(MediaPlaybackType::Music)?;
         let properties = self.display_updater.MusicProperties()?;
 
-        if let Some(title) = metadata.title {
+        if let Some(title) = metadata.title.as_deref() {
             properties.SetTitle(&HSTRING::from(title))?;
         }
-        if let Some(artist) = metadata.artist {
+        if let Some(artist) = metadata.artist.as_deref() {
             properties.SetArtist(&HSTRING::from(artist))?;
+        } else if !metadata.artists.is_empty() {
+            properties.SetArtist(&HSTRING::from(metadata.artists.join(", ")))?;
         }
-        if let Some(album) = metadata.album {
+        if let Some(album) = metadata.album.as_deref() {
             properties.SetAlbumTitle(&HSTRING::from(album))?;
         }
-        if let Some(url) = metadata.cover_url {
+        if !metadata.album_artists.is_empty() {
+            properties.SetAlbumArtist(&HSTRING::from(metadata.album_artists.join(", ")))?;
+        }
+        if let Some(track_number) = metadata.track_number.and_then(|n| u32::try_from(n).ok()) {
+            properties.SetTrackNumber(track_number)?;
+        }
+        let genres = properties.Genres()?;
+        genres.Clear()?;
+        for genre in &metadata.genres {
+            genres.Append(&HSTRING::from(genre.as_str()))?;
+        }
+        if let Some(url) = metadata.cover_url.as_deref() {
             let stream = if url.starts_with("file://") {
                 // url is a file, load it manually
                 let path = url.trim_start_matches("file://");
@@ -215,6 +310,66 @@ impl MediaControls {
         self.controls
             .UpdateTimelineProperties(&self.timeline_properties)?;
         self.display_updater.Update()?;
+        self.metadata = metadata;
+        Ok(())
+    }
+
+    /// Set whether shuffle is enabled.
+    fn set_shuffle(&mut self, shuffle: bool) -> Result<(), Error> {
+        self.controls.SetShuffleEnabled(shuffle)?;
         Ok(())
     }
+
+    /// Set the loop/repeat status.
+    fn set_loop_status(&mut self, loop_status: LoopStatus) -> Result<(), Error> {
+        let mode = match loop_status {
+            LoopStatus::None => MediaPlaybackAutoRepeatMode::None,
+            LoopStatus::Track => MediaPlaybackAutoRepeatMode::Track,
+            LoopStatus::Playlist => MediaPlaybackAutoRepeatMode::List,
+        };
+        self.controls.SetAutoRepeatMode(mode)?;
+        Ok(())
+    }
+
+    /// Set the playback rate, where 1.0 is the normal speed.
+    fn set_rate(&mut self, rate: f64) -> Result<(), Error> {
+        self.controls.SetPlaybackRate(rate)?;
+        Ok(())
+    }
+
+    /// Set the actions supported by the media player.
+    fn set_capabilities(&mut self, capabilities: Capabilities) -> Result<(), Error> {
+        let can_control = capabilities.can_control;
+
+        self.controls
+            .SetIsPlayEnabled(can_control && capabilities.can_play)?;
+        self.controls
+            .SetIsPauseEnabled(can_control && capabilities.can_pause)?;
+        self.controls.SetIsStopEnabled(can_control)?;
+        self.controls
+            .SetIsNextEnabled(can_control && capabilities.can_go_next)?;
+        self.controls
+            .SetIsPreviousEnabled(can_control && capabilities.can_go_previous)?;
+        self.controls
+            .SetIsFastForwardEnabled(can_control && capabilities.can_seek)?;
+        self.controls
+            .SetIsRewindEnabled(can_control && capabilities.can_seek)?;
+        self.capabilities = capabilities;
+        Ok(())
+    }
+
+    /// The metadata currently published.
+    fn metadata(&self) -> OwnedMediaMetadata {
+        self.metadata.clone()
+    }
+
+    /// The playback status currently published.
+    fn playback(&self) -> MediaPlayback {
+        self.playback.clone()
+    }
+
+    /// The actions currently published as supported.
+    fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
 }
@@ -0,0 +1,189 @@
+// Each test binary only uses some of the helpers.
+#![allow(dead_code, unused_macros)]
+
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dbus::blocking::{Connection, Proxy};
+use dbus::channel::Channel;
+use dbus::message::{MatchRule, MessageType};
+use dbus::Message;
+use souvlaki::{DbusConnection, MediaControlEvent, MediaControls, PlatformConfig};
+
+/// Defines a test of each check for every enabled backend, e.g. `backend_tests!(check_names)`
+/// defines `dbus_backend::check_names` and `zbus_backend::check_names`. A check is a
+/// `fn(Backend, Bus)`, and is skipped if `dbus-daemon` isn't installed.
+macro_rules! backend_tests {
+    ($($check:ident),* $(,)?) => {
+        #[cfg(feature = "use_dbus")]
+        mod dbus_backend {
+            $(
+                #[test]
+                fn $check() {
+                    crate::common::run(stringify!($check), crate::common::Backend::Dbus, super::$check);
+                }
+            )*
+        }
+
+        #[cfg(feature = "use_zbus")]
+        mod zbus_backend {
+            $(
+                #[test]
+                fn $check() {
+                    crate::common::run(stringify!($check), crate::common::Backend::Zbus, super::$check);
+                }
+            )*
+        }
+    };
+}
+
+/// An MPRIS backend under test.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Backend {
+    #[cfg(feature = "use_dbus")]
+    Dbus,
+    #[cfg(feature = "use_zbus")]
+    Zbus,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            #[cfg(feature = "use_dbus")]
+            Backend::Dbus => "dbus",
+            #[cfg(feature = "use_zbus")]
+            Backend::Zbus => "zbus",
+        }
+    }
+
+    /// Creates media controls which use this backend.
+    pub fn controls(self, config: PlatformConfig) -> MediaControls {
+        match self {
+            #[cfg(feature = "use_dbus")]
+            Backend::Dbus => {
+                MediaControls::from_backend(souvlaki::DbusBackend::new(config).unwrap())
+            }
+            #[cfg(feature = "use_zbus")]
+            Backend::Zbus => {
+                MediaControls::from_backend(souvlaki::ZbusBackend::new(config).unwrap())
+            }
+        }
+    }
+}
+
+/// Runs a check of `backend` on a private bus named after the check and the backend.
+pub fn run(name: &str, backend: Backend, check: fn(Backend, Bus)) {
+    match Bus::start(&format!("{}-{}", name, backend.name())) {
+        Some(bus) => check(backend, bus),
+        None => eprintln!("no dbus-daemon, skipping"),
+    }
+}
+
+/// Waits for the next event sent to the handler.
+pub fn next_event(received: &mpsc::Receiver<MediaControlEvent>) -> MediaControlEvent {
+    received.recv_timeout(Duration::from_secs(10)).unwrap()
+}
+
+/// A private `dbus-daemon` listening on a socket, stopped when dropped.
+pub struct Bus {
+    daemon: Child,
+    name: String,
+    path: PathBuf,
+}
+
+impl Bus {
+    /// Starts a bus listening on a socket named after `name`, or returns `None` if
+    /// `dbus-daemon` isn't installed.
+    pub fn start(name: &str) -> Option<Self> {
+        let path =
+            std::env::temp_dir().join(format!("souvlaki-test-bus-{}-{}", name, std::process::id()));
+        let daemon = Command::new("dbus-daemon")
+            .arg("--session")
+            .arg(format!("--address=unix:path={}", path.display()))
+            .args(["--nofork", "--nopidfile"])
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let bus = Self {
+            daemon,
+            name: name.to_string(),
+            path,
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !bus.path.exists() {
+            assert!(Instant::now() < deadline, "dbus-daemon didn't start");
+            thread::sleep(Duration::from_millis(10));
+        }
+        Some(bus)
+    }
+
+    /// The name the bus was started with, to start it again.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn address(&self) -> String {
+        format!("unix:path={}", self.path.display())
+    }
+
+    /// The config of media controls published on this bus as `org.mpris.MediaPlayer2.<dbus_name>`.
+    pub fn config<'a>(&self, dbus_name: &'a str) -> PlatformConfig<'a> {
+        PlatformConfig {
+            dbus_name,
+            display_name: "Souvlaki test",
+            dbus_connection: DbusConnection::Address(self.address()),
+            ..Default::default()
+        }
+    }
+
+    /// Connects a client to the bus.
+    pub fn client(&self) -> Connection {
+        let mut channel = Channel::open_private(&self.address()).unwrap();
+        channel.register().unwrap();
+        Connection::from(channel)
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        self.daemon.kill().ok();
+        self.daemon.wait().ok();
+        std::fs::remove_file(&self.path).ok();
+    }
+}
+
+/// A proxy to the MPRIS object of `org.mpris.MediaPlayer2.<dbus_name>`.
+pub fn mpris_proxy<'a>(client: &'a Connection, dbus_name: &str) -> Proxy<'a, &'a Connection> {
+    client.with_proxy(
+        format!("org.mpris.MediaPlayer2.{}", dbus_name),
+        "/org/mpris/MediaPlayer2",
+        Duration::from_secs(5),
+    )
+}
+
+/// Subscribes a client to the signals of the MPRIS object, to be read with [`signals`].
+pub fn watch_signals(client: &Connection) {
+    let rule = MatchRule::new()
+        .with_type(MessageType::Signal)
+        .with_path("/org/mpris/MediaPlayer2");
+    client.add_match_no_cb(&rule.match_str()).unwrap();
+}
+
+/// The signals of the MPRIS object received until the bus stays quiet for a moment.
+pub fn signals(client: &Connection) -> Vec<Message> {
+    let mut signals = Vec::new();
+    while let Some(message) = client
+        .channel()
+        .blocking_pop_message(Duration::from_millis(200))
+        .unwrap()
+    {
+        if message.path().as_deref() == Some("/org/mpris/MediaPlayer2") {
+            signals.push(message);
+        }
+    }
+    signals
+}
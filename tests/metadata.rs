@@ -0,0 +1,55 @@
+//! Converts metadata between its borrowed and owned forms.
+
+use std::time::Duration;
+
+use souvlaki::{MediaMetadata, MetadataValue, OwnedMediaMetadata};
+
+fn metadata() -> OwnedMediaMetadata {
+    MediaMetadata {
+        track_id: Some("/org/example/track/1"),
+        title: Some("Title"),
+        artists: &["A", "B"],
+        duration: Some(Duration::from_millis(61_500)),
+        user_rating: Some(0.5),
+        custom: &[
+            ("example:source", MetadataValue::String("radio")),
+            ("example:tags", MetadataValue::StringList(&["x", "y"])),
+        ],
+        ..Default::default()
+    }
+    .into()
+}
+
+#[test]
+fn converts_both_ways() {
+    let owned = metadata();
+    assert_eq!(owned.title.as_deref(), Some("Title"));
+    assert_eq!(owned.artists, ["A", "B"]);
+
+    let copy = owned.with_borrowed(|borrowed| {
+        assert_eq!(borrowed.track_id, Some("/org/example/track/1"));
+        assert_eq!(borrowed.artists, ["A", "B"]);
+        assert_eq!(
+            borrowed.custom[1],
+            ("example:tags", MetadataValue::StringList(&["x", "y"]))
+        );
+        OwnedMediaMetadata::from(borrowed)
+    });
+    assert_eq!(copy, owned);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn round_trips_through_serde() {
+    let owned = metadata();
+    let json = serde_json::to_string(&owned).unwrap();
+    assert_eq!(
+        serde_json::from_str::<OwnedMediaMetadata>(&json).unwrap(),
+        owned
+    );
+
+    // Missing fields are left empty, so that saved metadata can still be loaded later on.
+    let partial: OwnedMediaMetadata = serde_json::from_str(r#"{"title":"Title"}"#).unwrap();
+    assert_eq!(partial.title.as_deref(), Some("Title"));
+    assert_eq!(partial.duration, None);
+}
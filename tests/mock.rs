@@ -0,0 +1,183 @@
+//! Exercises the `mock` backend the way an application's tests would.
+#![cfg(feature = "mock")]
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use souvlaki::{
+    EventError, FieldPatch, MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback,
+    MediaPosition, MetadataPatch, MockBackend, MockCall, OwnedMediaMetadata,
+};
+
+#[test]
+fn records_calls_and_state() {
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Title"),
+            artists: &["A", "B"],
+            duration: Some(Duration::from_secs(60)),
+            ..Default::default()
+        })
+        .unwrap();
+    let playing = MediaPlayback::Playing {
+        progress: Some(MediaPosition(Duration::from_secs(5))),
+    };
+    controls.set_playback(playing.clone()).unwrap();
+    controls.set_volume(0.5).unwrap();
+
+    let metadata = OwnedMediaMetadata {
+        title: Some("Title".to_string()),
+        artists: vec!["A".to_string(), "B".to_string()],
+        duration: Some(Duration::from_secs(60)),
+        ..Default::default()
+    };
+    assert_eq!(
+        mock.calls(),
+        [
+            MockCall::SetMetadata(metadata.clone()),
+            MockCall::SetPlayback(playing.clone()),
+            MockCall::SetVolume(0.5),
+        ]
+    );
+
+    let state = mock.state();
+    assert_eq!(state.metadata, metadata);
+    assert_eq!(state.playback, playing);
+    assert_eq!(state.volume, 0.5);
+    assert!(!state.attached);
+
+    assert_eq!(controls.metadata(), metadata);
+    assert_eq!(controls.playback(), playing);
+    assert_eq!(controls.volume(), 0.5);
+}
+
+#[test]
+fn emits_events_to_the_handler() {
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+    assert!(!mock.emit(MediaControlEvent::Play));
+
+    let (tx, rx) = mpsc::channel();
+    controls
+        .attach(move |event| tx.send(event).unwrap())
+        .unwrap();
+    assert!(mock.state().attached);
+
+    assert!(mock.emit(MediaControlEvent::Play));
+    assert!(mock.emit(MediaControlEvent::SetVolume(0.2)));
+    assert_eq!(
+        rx.try_iter().collect::<Vec<_>>(),
+        [MediaControlEvent::Play, MediaControlEvent::SetVolume(0.2)]
+    );
+
+    controls.detach().unwrap();
+    assert!(!mock.emit(MediaControlEvent::Pause));
+}
+
+#[test]
+fn replies_with_the_handler_result() {
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+    assert_eq!(mock.request(MediaControlEvent::Play), None);
+
+    controls
+        .attach(|event| match event {
+            MediaControlEvent::Next => Err(EventError::NoSuchContent),
+            _ => Ok(()),
+        })
+        .unwrap();
+    assert_eq!(mock.request(MediaControlEvent::Play), Some(Ok(())));
+    assert_eq!(
+        mock.request(MediaControlEvent::Next),
+        Some(Err(EventError::NoSuchContent))
+    );
+}
+
+#[test]
+fn isolates_handler_panics() {
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+
+    let mut played = 0;
+    controls
+        .attach(move |event| match event {
+            MediaControlEvent::Play => played += 1,
+            MediaControlEvent::Stop => panic!("stopped {} times", played),
+            _ => {}
+        })
+        .unwrap();
+
+    assert_eq!(mock.request(MediaControlEvent::Play), Some(Ok(())));
+    assert_eq!(
+        mock.request(MediaControlEvent::Stop),
+        Some(Err(EventError::Failed(
+            "event handler panicked: stopped 1 times".to_string()
+        )))
+    );
+    // The handler keeps receiving events after a panic.
+    assert_eq!(mock.request(MediaControlEvent::Play), Some(Ok(())));
+}
+
+#[test]
+fn applies_metadata_patches() {
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Title"),
+            album: Some("Album"),
+            duration: Some(Duration::from_secs(60)),
+            ..Default::default()
+        })
+        .unwrap();
+    mock.clear_calls();
+
+    controls
+        .update_metadata(MetadataPatch {
+            title: FieldPatch::Set("Other title".to_string()),
+            album: FieldPatch::Clear,
+            ..Default::default()
+        })
+        .unwrap();
+    let metadata = OwnedMediaMetadata {
+        title: Some("Other title".to_string()),
+        duration: Some(Duration::from_secs(60)),
+        ..Default::default()
+    };
+    assert_eq!(mock.calls(), [MockCall::SetMetadata(metadata)]);
+
+    // Patches which don't change anything aren't published.
+    mock.clear_calls();
+    controls
+        .update_metadata(MetadataPatch {
+            album: FieldPatch::Clear,
+            ..Default::default()
+        })
+        .unwrap();
+    assert_eq!(mock.calls(), []);
+}
+
+#[test]
+#[cfg(all(
+    unix,
+    not(any(target_os = "macos", target_os = "ios", target_os = "android"))
+))]
+fn simulates_failures() {
+    use souvlaki::Error;
+
+    let mock = MockBackend::new();
+    let mut controls = MediaControls::from_backend(mock.clone());
+
+    mock.fail_next_call(Error::ThreadNotRunning);
+    assert!(matches!(
+        controls.set_volume(0.5),
+        Err(Error::ThreadNotRunning)
+    ));
+    controls.set_volume(0.7).unwrap();
+
+    assert_eq!(mock.calls(), [MockCall::SetVolume(0.7)]);
+    assert_eq!(mock.state().volume, 0.7);
+}
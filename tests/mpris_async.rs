@@ -0,0 +1,60 @@
+//! Checks that `AsyncMediaControls` are served by the future returned from `attach`.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(all(target_os = "linux", feature = "async"))]
+
+mod common;
+
+use std::thread;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use futures_lite::future::{self, block_on};
+use futures_lite::StreamExt;
+use souvlaki::{AsyncMediaControls, MediaControlEvent, MediaPlayback};
+
+use common::{mpris_proxy, Backend, Bus};
+
+#[test]
+fn serves_on_the_runtime_of_the_application() {
+    common::run("async", Backend::Zbus, serves_async_controls);
+}
+
+fn serves_async_controls(_: Backend, bus: Bus) {
+    let mut controls = AsyncMediaControls::new(bus.config("souvlaki_async_test")).unwrap();
+    let service = block_on(controls.attach()).unwrap();
+    // Stands for the runtime of the application.
+    let runtime = thread::spawn(move || block_on(service));
+
+    block_on(controls.set_playback(MediaPlayback::Playing { progress: None })).unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_async_test");
+    let status: String = proxy
+        .get("org.mpris.MediaPlayer2.Player", "PlaybackStatus")
+        .unwrap();
+    assert_eq!(status, "Playing");
+
+    let () = proxy
+        .method_call("org.mpris.MediaPlayer2.Player", "Pause", ())
+        .unwrap();
+    // The event is queued before the reply is sent.
+    let mut events = controls.events();
+    let event = block_on(future::poll_once(events.next()));
+    assert_eq!(event, Some(Some(MediaControlEvent::Pause)));
+
+    // Detaching removes the controls and stops the service.
+    block_on(controls.detach()).unwrap();
+    runtime.join().unwrap();
+    let identity: Result<String, _> = proxy.get("org.mpris.MediaPlayer2", "Identity");
+    assert!(identity.is_err());
+
+    // So does dropping them, once the service notices it.
+    let service = block_on(controls.attach()).unwrap();
+    let runtime = thread::spawn(move || block_on(service));
+    let identity: String = proxy.get("org.mpris.MediaPlayer2", "Identity").unwrap();
+    assert_eq!(identity, "Souvlaki test");
+    drop(controls);
+    runtime.join().unwrap();
+    let identity: Result<String, _> = proxy.get("org.mpris.MediaPlayer2", "Identity");
+    assert!(identity.is_err());
+}
@@ -0,0 +1,199 @@
+//! Checks that the MPRIS backends only announce the properties whose value changed.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use dbus::arg::PropMap;
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use souvlaki::{
+    Capabilities, FieldPatch, LoopStatus, MediaControlEvent, MediaMetadata, MediaPlayback,
+    MediaPlaylist, MetadataPatch, PlatformConfig, PlaylistOrdering,
+};
+
+use common::{mpris_proxy, next_event, Backend, Bus};
+
+backend_tests!(
+    only_changed_properties_are_announced,
+    written_properties_are_announced_once_confirmed
+);
+
+fn only_changed_properties_are_announced(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_changes_test"));
+    controls.attach(|_| {}).unwrap();
+
+    let client = bus.client();
+
+    let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_path("/org/mpris/MediaPlayer2");
+    client.add_match_no_cb(&rule.match_str()).unwrap();
+
+    let metadata = MediaMetadata {
+        title: Some("Title"),
+        album: Some("Album"),
+        ..Default::default()
+    };
+    controls.set_metadata(metadata.clone()).unwrap();
+    assert_eq!(changed_properties(&client), [vec!["Metadata".to_string()]]);
+
+    // Identical updates are skipped.
+    controls.set_metadata(metadata).unwrap();
+    controls.set_volume(1.0).unwrap();
+    controls
+        .update_metadata(MetadataPatch {
+            title: FieldPatch::Set("Title".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+    controls.set_playback(MediaPlayback::Stopped).unwrap();
+    assert!(changed_properties(&client).is_empty());
+
+    // A patch only changes the fields it sets or clears.
+    controls
+        .update_metadata(MetadataPatch {
+            album: FieldPatch::Clear,
+            ..Default::default()
+        })
+        .unwrap();
+    controls.set_volume(0.5).unwrap();
+    assert_eq!(
+        changed_properties(&client),
+        [vec!["Metadata".to_string()], vec!["Volume".to_string()]]
+    );
+    let published = controls.metadata();
+    assert_eq!(published.title.as_deref(), Some("Title"));
+    assert_eq!(published.album, None);
+
+    let playlist = MediaPlaylist {
+        id: "/playlist/1",
+        name: "Playlist",
+        icon: None,
+    };
+    controls.set_playlists(&[playlist]).unwrap();
+    controls.set_active_playlist(Some("/playlist/1")).unwrap();
+    controls
+        .set_playlist_orderings(&[PlaylistOrdering::Alphabetical])
+        .unwrap();
+    assert_eq!(
+        changed_properties(&client),
+        [
+            vec!["PlaylistCount".to_string()],
+            vec!["ActivePlaylist".to_string()],
+            vec!["Orderings".to_string()]
+        ]
+    );
+
+    // The active playlist is only announced if the playlist it refers to changes.
+    controls.set_active_playlist(Some("/playlist/1")).unwrap();
+    controls
+        .set_active_playlist(Some("/playlist/missing"))
+        .unwrap();
+    controls.set_active_playlist(None).unwrap();
+    assert_eq!(
+        changed_properties(&client),
+        [vec!["ActivePlaylist".to_string()]]
+    );
+    controls
+        .set_active_playlist(Some("/playlist/missing"))
+        .unwrap();
+    controls
+        .set_playlist_orderings(&[PlaylistOrdering::Alphabetical])
+        .unwrap();
+    assert!(changed_properties(&client).is_empty());
+
+    controls.detach().unwrap();
+}
+
+fn written_properties_are_announced_once_confirmed(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(PlatformConfig {
+        capabilities: Capabilities {
+            can_set_fullscreen: true,
+            ..Default::default()
+        },
+        ..bus.config("souvlaki_writes_test")
+    });
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+    controls.set_rate_range(0.5, 2.0).unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_writes_test");
+    let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_path("/org/mpris/MediaPlayer2");
+    client.add_match_no_cb(&rule.match_str()).unwrap();
+    changed_properties(&client);
+
+    // Writing a property only asks the application to change it.
+    let player = "org.mpris.MediaPlayer2.Player";
+    proxy.set(player, "Volume", 0.5).unwrap();
+    proxy.set(player, "Shuffle", true).unwrap();
+    proxy.set(player, "LoopStatus", "Track").unwrap();
+    proxy.set(player, "Rate", 4.0).unwrap();
+    proxy.set(player, "Rate", 1.5).unwrap();
+    proxy
+        .set("org.mpris.MediaPlayer2", "Fullscreen", true)
+        .unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::SetVolume(0.5));
+    assert_eq!(next_event(&received), MediaControlEvent::SetShuffle(true));
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::SetLoopStatus(LoopStatus::Track)
+    );
+    // The rate out of range is ignored.
+    assert_eq!(next_event(&received), MediaControlEvent::SetRate(1.5));
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::SetFullscreen(true)
+    );
+    assert!(changed_properties(&client).is_empty());
+    let volume: f64 = proxy.get(player, "Volume").unwrap();
+    assert_eq!(volume, 1.0);
+
+    // The properties are announced once the application confirms them.
+    controls.set_volume(0.5).unwrap();
+    controls.set_shuffle(true).unwrap();
+    controls.set_loop_status(LoopStatus::Track).unwrap();
+    controls.set_rate(1.5).unwrap();
+    controls.set_fullscreen(true).unwrap();
+    assert_eq!(
+        changed_properties(&client),
+        [
+            vec!["Volume".to_string()],
+            vec!["Shuffle".to_string()],
+            vec!["LoopStatus".to_string()],
+            vec!["Rate".to_string()],
+            vec!["Fullscreen".to_string()],
+        ]
+    );
+
+    controls.detach().unwrap();
+}
+
+/// The names of the properties announced by each `PropertiesChanged` signal received until
+/// the bus stays quiet for a moment.
+fn changed_properties(client: &Connection) -> Vec<Vec<String>> {
+    let mut signals = Vec::new();
+    while let Some(message) = client
+        .channel()
+        .blocking_pop_message(Duration::from_millis(200))
+        .unwrap()
+    {
+        if message.member().as_deref() != Some("PropertiesChanged") {
+            continue;
+        }
+        let (_, changed): (String, PropMap) = message.read2().unwrap();
+        let mut names: Vec<String> = changed.into_keys().collect();
+        names.sort();
+        signals.push(names);
+    }
+    signals
+}
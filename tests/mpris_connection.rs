@@ -0,0 +1,158 @@
+//! Checks that the MPRIS services can be published on a given bus or connection, and served
+//! by the executor of the application.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+
+use common::{Backend, Bus};
+
+backend_tests!(publishes_on_a_bus_address);
+
+fn publishes_on_a_bus_address(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_address_test"));
+    controls.attach(|_| ()).unwrap();
+
+    let client = bus.client();
+    assert_eq!(
+        identity(&client, "org.mpris.MediaPlayer2.souvlaki_address_test").unwrap(),
+        "Souvlaki test"
+    );
+
+    controls.detach().unwrap();
+}
+
+#[cfg(feature = "use_zbus")]
+#[test]
+fn zbus_shares_the_connection_of_the_application() {
+    common::run(
+        "shared",
+        Backend::Zbus,
+        shares_the_connection_of_the_application,
+    );
+}
+
+#[cfg(feature = "use_zbus")]
+fn shares_the_connection_of_the_application(_: Backend, bus: Bus) {
+    use futures_lite::future::block_on;
+    use souvlaki::{DbusConnection, Error, MediaControls, PlatformConfig, ZbusBackend};
+
+    let address = bus.address();
+    let builder = zbus::ConnectionBuilder::address(address.as_str()).unwrap();
+    let connection = block_on(builder.build()).unwrap();
+    let config = PlatformConfig {
+        dbus_name: "souvlaki_shared_test",
+        display_name: "Shared test",
+        dbus_connection: DbusConnection::Zbus(connection.clone()),
+        ..Default::default()
+    };
+    let mut controls = MediaControls::from_backend(ZbusBackend::new(config.clone()).unwrap());
+    controls.attach(|_| ()).unwrap();
+
+    let client = bus.client();
+    let name = "org.mpris.MediaPlayer2.souvlaki_shared_test";
+    assert_eq!(identity(&client, name).unwrap(), "Shared test");
+    assert_eq!(
+        owner(&client, name).as_deref(),
+        connection.unique_name().map(|name| name.as_str())
+    );
+
+    // Only one instance can be served on a connection.
+    let mut other = MediaControls::from_backend(ZbusBackend::new(config.clone()).unwrap());
+    assert!(matches!(other.attach(|_| ()), Err(Error::AlreadyServed)));
+
+    // The D-Bus backend can't use the connection.
+    #[cfg(feature = "use_dbus")]
+    assert!(matches!(
+        souvlaki::DbusBackend::new(config),
+        Err(Error::ZbusConnectionUnsupported)
+    ));
+
+    // Detaching removes the controls, and leaves the connection open.
+    controls.detach().unwrap();
+    assert_eq!(owner(&client, name), None);
+    let unique_name = connection.unique_name().unwrap().to_string();
+    assert!(owner(&client, &unique_name).is_some());
+    assert!(identity(&client, &unique_name).is_err());
+}
+
+#[cfg(feature = "use_zbus")]
+#[test]
+fn zbus_serves_on_the_executor_of_the_application() {
+    common::run(
+        "future",
+        Backend::Zbus,
+        serves_on_the_executor_of_the_application,
+    );
+}
+
+#[cfg(feature = "use_zbus")]
+fn serves_on_the_executor_of_the_application(_: Backend, bus: Bus) {
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Instant;
+
+    use futures_lite::future::block_on;
+    use souvlaki::{MediaControlEvent, MediaControlsBackend, MediaPlayback, ZbusBackend};
+
+    let (events, received) = mpsc::channel();
+    let mut backend = ZbusBackend::new(bus.config("souvlaki_future_test")).unwrap();
+    let service = backend
+        .attach_future(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    // Nothing is published until the application runs the future.
+    let client = bus.client();
+    let name = "org.mpris.MediaPlayer2.souvlaki_future_test";
+    assert!(identity(&client, name).is_err());
+    let executor = thread::spawn(move || block_on(service));
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while identity(&client, name).is_err() {
+        assert!(Instant::now() < deadline, "the controls weren't published");
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(identity(&client, name).unwrap(), "Souvlaki test");
+
+    backend
+        .set_playback(MediaPlayback::Playing { progress: None })
+        .unwrap();
+    let proxy = client.with_proxy(name, "/org/mpris/MediaPlayer2", Duration::from_secs(5));
+    let status: String = proxy
+        .get("org.mpris.MediaPlayer2.Player", "PlaybackStatus")
+        .unwrap();
+    assert_eq!(status, "Playing");
+    let () = proxy
+        .method_call("org.mpris.MediaPlayer2.Player", "Pause", ())
+        .unwrap();
+    assert_eq!(common::next_event(&received), MediaControlEvent::Pause);
+
+    // Detaching completes the future.
+    backend.detach().unwrap();
+    executor.join().unwrap().unwrap();
+    assert!(identity(&client, name).is_err());
+}
+
+fn identity(client: &Connection, name: &str) -> Result<String, dbus::Error> {
+    let proxy = client.with_proxy(name, "/org/mpris/MediaPlayer2", Duration::from_secs(5));
+    proxy.get("org.mpris.MediaPlayer2", "Identity")
+}
+
+#[cfg(feature = "use_zbus")]
+fn owner(client: &Connection, name: &str) -> Option<String> {
+    let proxy = client.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_secs(5),
+    );
+    proxy
+        .method_call("org.freedesktop.DBus", "GetNameOwner", (name,))
+        .map(|(owner,): (String,)| owner)
+        .ok()
+}
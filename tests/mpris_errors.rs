@@ -0,0 +1,65 @@
+//! Checks that the errors of the event handler are sent back to MPRIS clients.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use souvlaki::{EventError, MediaControlEvent};
+
+use common::{mpris_proxy, Backend, Bus};
+
+backend_tests!(replies_with_handler_errors);
+
+fn replies_with_handler_errors(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_errors_test"));
+    controls
+        .attach(|event| match event {
+            MediaControlEvent::Next => Err(EventError::NoSuchContent),
+            MediaControlEvent::Previous => Err(EventError::Failed("no history".to_string())),
+            MediaControlEvent::SetVolume(_) => Err(EventError::NotSupported),
+            MediaControlEvent::Stop => panic!("cannot stop"),
+            _ => Ok(()),
+        })
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_errors_test");
+    let call = |method: &str| -> Result<(), dbus::Error> {
+        proxy.method_call("org.mpris.MediaPlayer2.Player", method, ())
+    };
+
+    assert!(call("Play").is_ok());
+
+    let error = call("Next").unwrap_err();
+    assert_eq!(
+        error.name(),
+        Some("org.mpris.MediaPlayer2.Error.NoSuchContent")
+    );
+
+    let error = call("Previous").unwrap_err();
+    assert_eq!(error.name(), Some("org.mpris.MediaPlayer2.Error.Failed"));
+    assert_eq!(error.message(), Some("no history"));
+
+    // A panic of the handler fails the call, but not the service.
+    let error = call("Stop").unwrap_err();
+    assert_eq!(error.name(), Some("org.mpris.MediaPlayer2.Error.Failed"));
+    assert_eq!(error.message(), Some("event handler panicked: cannot stop"));
+    assert!(call("Play").is_ok());
+
+    let property_error = match backend {
+        #[cfg(feature = "use_dbus")]
+        Backend::Dbus => "org.freedesktop.DBus.Error.NotSupported",
+        // zbus 3 can't name the errors of property setters.
+        #[cfg(feature = "use_zbus")]
+        Backend::Zbus => "org.freedesktop.zbus.Error",
+    };
+    let error = proxy
+        .set("org.mpris.MediaPlayer2.Player", "Volume", 0.5)
+        .unwrap_err();
+    assert_eq!(error.name(), Some(property_error));
+
+    controls.detach().unwrap();
+}
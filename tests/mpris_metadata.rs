@@ -0,0 +1,174 @@
+//! Checks the metadata published by the MPRIS backends: the types of the standard entries,
+//! and that custom entries can't override them.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::time::Duration;
+
+use dbus::arg::{self, PropMap, RefArg};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::Path;
+use souvlaki::{Error, MediaMetadata, MetadataValue};
+
+use common::{mpris_proxy, Backend, Bus};
+
+backend_tests!(publishes_the_xesam_fields, rejects_reserved_metadata_keys);
+
+fn publishes_the_xesam_fields(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_xesam_test"));
+    controls.attach(|_| ()).unwrap();
+
+    controls
+        .set_metadata(MediaMetadata {
+            track_id: Some("/track/1"),
+            title: Some("Title"),
+            album: Some("Album"),
+            artist: Some("Artist"),
+            cover_url: Some("file:///cover.png"),
+            duration: Some(Duration::from_secs(90)),
+            album_artists: &["Album Artist"],
+            composers: &["Composer"],
+            lyricists: &["Lyricist"],
+            genres: &["Jazz", "Blues"],
+            comments: &["Comment"],
+            track_number: Some(3),
+            disc_number: Some(2),
+            audio_bpm: Some(120),
+            content_created: Some("2007-04-29T14:35:51"),
+            first_used: Some("2007-04-29T14:35:51"),
+            last_used: Some("2007-04-29T14:35:51"),
+            use_count: Some(7),
+            user_rating: Some(0.8),
+            auto_rating: Some(0.5),
+            url: Some("file:///song.mp3"),
+            lyrics: Some("Lyrics"),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_xesam_test");
+    let metadata: PropMap = proxy
+        .get("org.mpris.MediaPlayer2.Player", "Metadata")
+        .unwrap();
+
+    // The types are the ones given by the specification, which clients rely on.
+    let mut types: Vec<(&str, String)> = metadata
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.0.signature().to_string()))
+        .collect();
+    types.sort();
+    let expected = [
+        ("mpris:artUrl", "s"),
+        ("mpris:length", "x"),
+        ("mpris:trackid", "o"),
+        ("xesam:album", "s"),
+        ("xesam:albumArtist", "as"),
+        ("xesam:artist", "as"),
+        ("xesam:asText", "s"),
+        ("xesam:audioBPM", "i"),
+        ("xesam:autoRating", "d"),
+        ("xesam:comment", "as"),
+        ("xesam:composer", "as"),
+        ("xesam:contentCreated", "s"),
+        ("xesam:discNumber", "i"),
+        ("xesam:firstUsed", "s"),
+        ("xesam:genre", "as"),
+        ("xesam:lastUsed", "s"),
+        ("xesam:lyricist", "as"),
+        ("xesam:title", "s"),
+        ("xesam:trackNumber", "i"),
+        ("xesam:url", "s"),
+        ("xesam:useCount", "i"),
+        ("xesam:userRating", "d"),
+    ];
+    let expected: Vec<(&str, String)> = expected
+        .iter()
+        .map(|(key, signature)| (*key, signature.to_string()))
+        .collect();
+    assert_eq!(types, expected);
+
+    let strings = |key| {
+        arg::prop_cast::<Vec<String>>(&metadata, key)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(strings("xesam:artist"), ["Artist"]);
+    assert_eq!(strings("xesam:albumArtist"), ["Album Artist"]);
+    assert_eq!(strings("xesam:genre"), ["Jazz", "Blues"]);
+    let integer = |key| *arg::prop_cast::<i32>(&metadata, key).unwrap();
+    assert_eq!(integer("xesam:trackNumber"), 3);
+    assert_eq!(integer("xesam:discNumber"), 2);
+    assert_eq!(integer("xesam:audioBPM"), 120);
+    assert_eq!(integer("xesam:useCount"), 7);
+    let double = |key| *arg::prop_cast::<f64>(&metadata, key).unwrap();
+    assert_eq!(double("xesam:userRating"), 0.8);
+    assert_eq!(double("xesam:autoRating"), 0.5);
+    assert_eq!(
+        arg::prop_cast::<i64>(&metadata, "mpris:length"),
+        Some(&90_000_000)
+    );
+
+    controls.detach().unwrap();
+}
+
+fn rejects_reserved_metadata_keys(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_metadata_test"));
+    controls.attach(|_| ()).unwrap();
+
+    controls
+        .set_metadata(MediaMetadata {
+            track_id: Some("/track/1"),
+            title: Some("Title"),
+            custom: &[("example:source", MetadataValue::String("radio"))],
+            ..Default::default()
+        })
+        .unwrap();
+
+    // The entries of the mpris and xesam namespaces come from the other fields.
+    for key in ["mpris:trackid", "xesam:title"] {
+        let custom = [(key, MetadataValue::String("/track/2"))];
+        let result = controls.set_metadata(MediaMetadata {
+            track_id: Some("/track/1"),
+            title: Some("Title"),
+            custom: &custom,
+            ..Default::default()
+        });
+        match result {
+            Err(Error::InvalidMetadataKey(invalid)) => assert_eq!(invalid, key),
+            result => panic!("expected InvalidMetadataKey, got {:?}", result),
+        }
+
+        let result = controls.insert_track(
+            "/track/2",
+            MediaMetadata {
+                custom: &custom,
+                ..Default::default()
+            },
+            None,
+        );
+        assert!(matches!(result, Err(Error::InvalidMetadataKey(_))));
+    }
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_metadata_test");
+    let metadata: PropMap = proxy
+        .get("org.mpris.MediaPlayer2.Player", "Metadata")
+        .unwrap();
+    let track_id: &Path = arg::prop_cast(&metadata, "mpris:trackid").unwrap();
+    assert_eq!(&**track_id, "/track/1");
+    assert_eq!(
+        arg::prop_cast::<String>(&metadata, "xesam:title").map(String::as_str),
+        Some("Title")
+    );
+    assert_eq!(
+        arg::prop_cast::<String>(&metadata, "example:source").map(String::as_str),
+        Some("radio")
+    );
+
+    controls.detach().unwrap();
+}
@@ -0,0 +1,70 @@
+//! Checks how the MPRIS services share their bus names with other applications.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+use std::time::Duration;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::RequestNameReply;
+use dbus::blocking::Connection;
+use souvlaki::{Error, MediaControlEvent, PlatformConfig};
+
+use common::{next_event, Backend, Bus};
+
+backend_tests!(shares_names);
+
+fn shares_names(backend: Backend, bus: Bus) {
+    let client = bus.client();
+    let bus_name = "org.mpris.MediaPlayer2.souvlaki_names_test";
+    let config = bus.config("souvlaki_names_test");
+
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(config.clone());
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    // A second instance doesn't take the name from the first one.
+    let mut other = backend.controls(config.clone());
+    match other.attach(|_| ()) {
+        Err(Error::NameTaken(name)) => assert_eq!(name, bus_name),
+        result => panic!("expected NameTaken, got {:?}", result),
+    }
+
+    // Unless it publishes a name of its own.
+    let mut unique = backend.controls(PlatformConfig {
+        unique_dbus_name: true,
+        ..config
+    });
+    unique.attach(|_| ()).unwrap();
+    let unique_name = format!("{}.instance{}", bus_name, std::process::id());
+    assert!(has_owner(&client, &unique_name));
+    unique.detach().unwrap();
+
+    // Another application may still take over the name, and give it back.
+    let reply = client.request_name(bus_name, false, true, true).unwrap();
+    assert_eq!(reply, RequestNameReply::PrimaryOwner);
+    assert_eq!(next_event(&received), MediaControlEvent::NameLost);
+
+    client.release_name(bus_name).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::NameAcquired);
+    assert!(has_owner(&client, bus_name));
+
+    controls.detach().unwrap();
+}
+
+fn has_owner(client: &Connection, name: &str) -> bool {
+    let proxy = client.with_proxy(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        Duration::from_secs(5),
+    );
+    let (has_owner,): (bool,) = proxy
+        .method_call("org.freedesktop.DBus", "NameHasOwner", (name,))
+        .unwrap();
+    has_owner
+}
@@ -0,0 +1,264 @@
+//! Checks the properties of the `org.mpris.MediaPlayer2.Player` interface: their values, the
+//! events sent when clients write them, and the signals announcing them. Also checks that
+//! the capabilities decide which requests reach the application.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+
+use dbus::arg::{self, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::Connection;
+use souvlaki::{Capabilities, Error, LoopStatus, MediaControlEvent, PlatformConfig};
+
+use common::{mpris_proxy, next_event, signals, watch_signals, Backend, Bus};
+
+const ROOT: &str = "org.mpris.MediaPlayer2";
+const PLAYER: &str = "org.mpris.MediaPlayer2.Player";
+const TRACKLIST: &str = "org.mpris.MediaPlayer2.TrackList";
+
+backend_tests!(shuffle_and_loop_status, playback_rate, capabilities);
+
+fn shuffle_and_loop_status(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(bus.config("souvlaki_shuffle_test"));
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_shuffle_test");
+    watch_signals(&client);
+
+    let shuffle: bool = proxy.get(PLAYER, "Shuffle").unwrap();
+    assert!(!shuffle);
+    let loop_status: String = proxy.get(PLAYER, "LoopStatus").unwrap();
+    assert_eq!(loop_status, "None");
+
+    // Clients ask the application to change them.
+    proxy.set(PLAYER, "Shuffle", true).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::SetShuffle(true));
+    proxy.set(PLAYER, "LoopStatus", "Playlist").unwrap();
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::SetLoopStatus(LoopStatus::Playlist)
+    );
+    assert!(proxy.set(PLAYER, "LoopStatus", "Forever").is_err());
+    assert!(received.try_recv().is_err());
+
+    // The application publishes them.
+    signals(&client);
+    controls.set_shuffle(true).unwrap();
+    controls.set_loop_status(LoopStatus::Playlist).unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 2);
+    assert_eq!(
+        arg::prop_cast::<bool>(&changed[0].1, "Shuffle"),
+        Some(&true)
+    );
+    assert_eq!(
+        arg::prop_cast::<String>(&changed[1].1, "LoopStatus").map(String::as_str),
+        Some("Playlist")
+    );
+    let shuffle: bool = proxy.get(PLAYER, "Shuffle").unwrap();
+    assert!(shuffle);
+    let loop_status: String = proxy.get(PLAYER, "LoopStatus").unwrap();
+    assert_eq!(loop_status, "Playlist");
+
+    controls.set_loop_status(LoopStatus::Track).unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(
+        arg::prop_cast::<String>(&changed[0].1, "LoopStatus").map(String::as_str),
+        Some("Track")
+    );
+
+    controls.detach().unwrap();
+}
+
+fn playback_rate(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(bus.config("souvlaki_rate_test"));
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_rate_test");
+    watch_signals(&client);
+
+    // Only the normal speed is allowed by default.
+    for name in ["Rate", "MinimumRate", "MaximumRate"] {
+        let rate: f64 = proxy.get(PLAYER, name).unwrap();
+        assert_eq!(rate, 1.0, "{}", name);
+    }
+    proxy.set(PLAYER, "Rate", 2.0).unwrap();
+    assert!(received.try_recv().is_err());
+    // A rate of 0.0 stands for a pause.
+    proxy.set(PLAYER, "Rate", 0.0).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::Pause);
+
+    signals(&client);
+    controls.set_rate_range(0.5, 2.0).unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(
+        arg::prop_cast::<f64>(&changed[0].1, "MinimumRate"),
+        Some(&0.5)
+    );
+    assert_eq!(
+        arg::prop_cast::<f64>(&changed[0].1, "MaximumRate"),
+        Some(&2.0)
+    );
+    let minimum: f64 = proxy.get(PLAYER, "MinimumRate").unwrap();
+    assert_eq!(minimum, 0.5);
+    let maximum: f64 = proxy.get(PLAYER, "MaximumRate").unwrap();
+    assert_eq!(maximum, 2.0);
+
+    // Clients may only request the rates within the range.
+    proxy.set(PLAYER, "Rate", 3.0).unwrap();
+    proxy.set(PLAYER, "Rate", 0.25).unwrap();
+    assert!(received.try_recv().is_err());
+    proxy.set(PLAYER, "Rate", 2.0).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::SetRate(2.0));
+
+    controls.set_rate(2.0).unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(arg::prop_cast::<f64>(&changed[0].1, "Rate"), Some(&2.0));
+    let rate: f64 = proxy.get(PLAYER, "Rate").unwrap();
+    assert_eq!(rate, 2.0);
+
+    // The application can't publish invalid rates.
+    for rate in [0.0, -1.0, f64::INFINITY] {
+        match controls.set_rate(rate) {
+            Err(Error::InvalidRate(invalid)) => assert_eq!(invalid, rate),
+            result => panic!("expected InvalidRate, got {:?}", result),
+        }
+    }
+    for (minimum, maximum) in [(1.5, 2.0), (0.5, 0.75), (0.0, 1.0)] {
+        assert!(matches!(
+            controls.set_rate_range(minimum, maximum),
+            Err(Error::InvalidRateRange(..))
+        ));
+    }
+    assert!(changed_properties(&client).is_empty());
+    let rate: f64 = proxy.get(PLAYER, "Rate").unwrap();
+    assert_eq!(rate, 2.0);
+
+    controls.detach().unwrap();
+}
+
+fn capabilities(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(PlatformConfig {
+        capabilities: Capabilities {
+            can_go_next: false,
+            can_quit: false,
+            ..Default::default()
+        },
+        ..bus.config("souvlaki_capabilities_test")
+    });
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_capabilities_test");
+    watch_signals(&client);
+
+    let expected = [
+        (ROOT, "CanQuit", false),
+        (ROOT, "CanRaise", true),
+        (ROOT, "CanSetFullscreen", false),
+        (PLAYER, "CanControl", true),
+        (PLAYER, "CanGoNext", false),
+        (PLAYER, "CanGoPrevious", true),
+        (PLAYER, "CanPlay", true),
+        (PLAYER, "CanPause", true),
+        (PLAYER, "CanSeek", true),
+        (TRACKLIST, "CanEditTracks", true),
+    ];
+    for (interface, name, value) in expected {
+        let published: bool = proxy.get(interface, name).unwrap();
+        assert_eq!(published, value, "{}", name);
+    }
+
+    // The calls to unsupported methods are ignored, as the specification asks.
+    let () = proxy.method_call(PLAYER, "Next", ()).unwrap();
+    let () = proxy.method_call(ROOT, "Quit", ()).unwrap();
+    let () = proxy.method_call(PLAYER, "Previous", ()).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::Previous);
+
+    signals(&client);
+    controls
+        .set_capabilities(Capabilities {
+            can_quit: true,
+            ..Default::default()
+        })
+        .unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 2);
+    assert_eq!(changed[0].0, ROOT);
+    assert_eq!(
+        arg::prop_cast::<bool>(&changed[0].1, "CanQuit"),
+        Some(&true)
+    );
+    assert_eq!(changed[1].0, PLAYER);
+    assert_eq!(
+        arg::prop_cast::<bool>(&changed[1].1, "CanGoNext"),
+        Some(&true)
+    );
+    assert_eq!(changed[1].1.len(), 1);
+    let () = proxy.method_call(PLAYER, "Next", ()).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::Next);
+
+    // Without control, none of the playback methods are supported.
+    controls
+        .set_capabilities(Capabilities {
+            can_control: false,
+            ..Default::default()
+        })
+        .unwrap();
+    let changed = changed_properties(&client);
+    assert_eq!(changed.len(), 1);
+    assert_eq!(changed[0].0, PLAYER);
+    let mut names: Vec<&str> = changed[0].1.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        [
+            "CanControl",
+            "CanGoNext",
+            "CanGoPrevious",
+            "CanPause",
+            "CanPlay",
+            "CanSeek"
+        ]
+    );
+    for name in names {
+        let published: bool = proxy.get(PLAYER, name).unwrap();
+        assert!(!published, "{}", name);
+    }
+    for method in ["Play", "Stop", "Next"] {
+        let () = proxy.method_call(PLAYER, method, ()).unwrap();
+    }
+    let () = proxy.method_call(ROOT, "Raise", ()).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::Raise);
+
+    controls.detach().unwrap();
+}
+
+/// The interface and the properties announced by each `PropertiesChanged` signal received
+/// until the bus stays quiet for a moment.
+fn changed_properties(client: &Connection) -> Vec<(String, PropMap)> {
+    signals(client)
+        .iter()
+        .filter(|message| message.member().as_deref() == Some("PropertiesChanged"))
+        .map(|message| message.read2().unwrap())
+        .collect()
+}
@@ -0,0 +1,192 @@
+//! Checks the `org.mpris.MediaPlayer2.Playlists` interface: the listing of the playlists,
+//! the active playlist and its signals.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use dbus::arg::PropMap;
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::{Connection, Proxy};
+use dbus::Path;
+use souvlaki::{MediaPlaylist, PlaylistOrdering};
+
+use common::{mpris_proxy, signals, watch_signals, Backend, Bus};
+
+const PLAYLISTS: &str = "org.mpris.MediaPlayer2.Playlists";
+
+backend_tests!(lists_the_playlists);
+
+fn lists_the_playlists(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_playlists_test"));
+    controls.attach(|_| ()).unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_playlists_test");
+    watch_signals(&client);
+
+    controls
+        .set_playlists(&[
+            playlist("/playlist/rock", "Rock"),
+            playlist("/playlist/chill", "Chill"),
+            playlist("/playlist/jazz", "Jazz"),
+        ])
+        .unwrap();
+    controls
+        .set_playlist_orderings(&[
+            PlaylistOrdering::Alphabetical,
+            PlaylistOrdering::UserDefined,
+        ])
+        .unwrap();
+    let orderings: Vec<String> = proxy.get(PLAYLISTS, "Orderings").unwrap();
+    assert_eq!(orderings, ["Alphabetical", "User"]);
+    let count: u32 = proxy.get(PLAYLISTS, "PlaylistCount").unwrap();
+    assert_eq!(count, 3);
+
+    // Only the alphabetical ordering sorts the playlists, the others keep the given order.
+    assert_eq!(
+        get_playlists(&proxy, 0, 10, "User", false),
+        ["Rock", "Chill", "Jazz"]
+    );
+    assert_eq!(
+        get_playlists(&proxy, 0, 10, "User", true),
+        ["Jazz", "Chill", "Rock"]
+    );
+    assert_eq!(
+        get_playlists(&proxy, 0, 10, "Created", false),
+        ["Rock", "Chill", "Jazz"]
+    );
+    assert_eq!(
+        get_playlists(&proxy, 0, 10, "Alphabetical", false),
+        ["Chill", "Jazz", "Rock"]
+    );
+    assert_eq!(
+        get_playlists(&proxy, 0, 10, "Alphabetical", true),
+        ["Rock", "Jazz", "Chill"]
+    );
+
+    // The index and the maximum count apply after ordering.
+    assert_eq!(get_playlists(&proxy, 1, 1, "Alphabetical", false), ["Jazz"]);
+    assert_eq!(
+        get_playlists(&proxy, 1, 5, "Alphabetical", true),
+        ["Jazz", "Chill"]
+    );
+    assert_eq!(
+        get_playlists(&proxy, 0, 0, "Alphabetical", false),
+        Vec::<String>::new()
+    );
+    assert_eq!(
+        get_playlists(&proxy, 3, 5, "User", false),
+        Vec::<String>::new()
+    );
+
+    // An unknown playlist isn't active.
+    assert_eq!(active_playlist(&proxy), None);
+    controls
+        .set_active_playlist(Some("/playlist/missing"))
+        .unwrap();
+    assert_eq!(active_playlist(&proxy), None);
+
+    controls
+        .set_active_playlist(Some("/playlist/rock"))
+        .unwrap();
+    assert_eq!(
+        active_playlist(&proxy),
+        Some(("/playlist/rock".to_string(), "Rock".to_string()))
+    );
+
+    // Renaming a playlist which is kept announces it, along with the active playlist.
+    signals(&client);
+    controls
+        .set_playlists(&[
+            playlist("/playlist/rock", "Metal"),
+            playlist("/playlist/chill", "Chill"),
+        ])
+        .unwrap();
+    assert_eq!(
+        playlists_signals(&client),
+        [
+            "PlaylistChanged /playlist/rock Metal",
+            "PropertiesChanged ActivePlaylist,PlaylistCount",
+        ]
+    );
+    assert_eq!(
+        active_playlist(&proxy),
+        Some(("/playlist/rock".to_string(), "Metal".to_string()))
+    );
+
+    // Removing the active playlist leaves no active playlist.
+    controls
+        .set_playlists(&[playlist("/playlist/chill", "Chill")])
+        .unwrap();
+    assert_eq!(
+        playlists_signals(&client),
+        ["PropertiesChanged ActivePlaylist,PlaylistCount"]
+    );
+    assert_eq!(active_playlist(&proxy), None);
+
+    controls.detach().unwrap();
+}
+
+fn playlist<'a>(id: &'a str, name: &'a str) -> MediaPlaylist<'a> {
+    MediaPlaylist {
+        id,
+        name,
+        icon: None,
+    }
+}
+
+/// Calls `GetPlaylists`, and returns the names of the playlists.
+fn get_playlists(
+    proxy: &Proxy<&Connection>,
+    index: u32,
+    max_count: u32,
+    order: &str,
+    reverse_order: bool,
+) -> Vec<String> {
+    let (playlists,): (Vec<(Path, String, String)>,) = proxy
+        .method_call(
+            PLAYLISTS,
+            "GetPlaylists",
+            (index, max_count, order, reverse_order),
+        )
+        .unwrap();
+    playlists.into_iter().map(|(_, name, _)| name).collect()
+}
+
+/// Returns the id and the name of the active playlist, if it is valid.
+fn active_playlist(proxy: &Proxy<&Connection>) -> Option<(String, String)> {
+    let (valid, (id, name, _)): (bool, (Path, String, String)) =
+        proxy.get(PLAYLISTS, "ActivePlaylist").unwrap();
+    if valid {
+        Some((id.to_string(), name))
+    } else {
+        assert_eq!(&*id, "/");
+        None
+    }
+}
+
+/// Describes the signals of the Playlists interface: the id and name of the changed
+/// playlists, and the sorted names of the changed properties.
+fn playlists_signals(client: &Connection) -> Vec<String> {
+    signals(client)
+        .iter()
+        .map(|message| match message.member().as_deref().unwrap() {
+            "PlaylistChanged" => {
+                let (id, name, _): (Path, String, String) = message.read1().unwrap();
+                format!("PlaylistChanged {} {}", id, name)
+            }
+            "PropertiesChanged" => {
+                let (interface, changed, invalidated): (String, PropMap, Vec<String>) =
+                    message.read3().unwrap();
+                assert_eq!(interface, PLAYLISTS);
+                let mut names: Vec<String> = changed.into_keys().chain(invalidated).collect();
+                names.sort();
+                format!("PropertiesChanged {}", names.join(","))
+            }
+            member => panic!("unexpected signal {}", member),
+        })
+        .collect()
+}
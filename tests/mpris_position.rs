@@ -0,0 +1,186 @@
+//! Checks that the MPRIS backends extrapolate the position while playing, announce the
+//! seeks, and only let clients set the position of the current track.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use dbus::arg::{self, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::{Connection, Proxy};
+use dbus::Path;
+use souvlaki::{MediaControlEvent, MediaMetadata, MediaPlayback, MediaPosition};
+
+use common::{mpris_proxy, next_event, signals, watch_signals, Backend, Bus};
+
+backend_tests!(
+    extrapolates_the_position,
+    announces_seeks,
+    ignores_stale_set_position
+);
+
+fn extrapolates_the_position(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_position_test"));
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_position_test");
+    let position = || -> Duration {
+        let micros: i64 = proxy
+            .get("org.mpris.MediaPlayer2.Player", "Position")
+            .unwrap();
+        Duration::from_micros(micros as u64)
+    };
+
+    controls.attach(|_| {}).unwrap();
+    controls
+        .set_metadata(MediaMetadata {
+            duration: Some(Duration::from_secs(20)),
+            ..Default::default()
+        })
+        .unwrap();
+
+    // The position advances while playing...
+    controls
+        .set_playback(MediaPlayback::Playing {
+            progress: Some(MediaPosition(Duration::from_secs(10))),
+        })
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    let playing = position();
+    assert!(playing >= Duration::from_millis(10_200), "{:?}", playing);
+    assert!(playing < Duration::from_secs(11), "{:?}", playing);
+
+    // ...but not while paused.
+    let paused = MediaPlayback::Paused {
+        progress: Some(MediaPosition(Duration::from_secs(12))),
+    };
+    controls.set_playback(paused.clone()).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(position(), Duration::from_secs(12));
+    assert_eq!(controls.playback(), paused);
+
+    // It advances at the current rate, up to the duration of the track.
+    controls.set_rate(2.0).unwrap();
+    controls
+        .set_playback(MediaPlayback::Playing {
+            progress: Some(MediaPosition(Duration::from_millis(19_800))),
+        })
+        .unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(position(), Duration::from_secs(20));
+    assert_eq!(
+        controls.playback(),
+        MediaPlayback::Playing {
+            progress: Some(MediaPosition(Duration::from_secs(20)))
+        }
+    );
+
+    controls.set_playback(MediaPlayback::Stopped).unwrap();
+    assert_eq!(position(), Duration::ZERO);
+
+    controls.detach().unwrap();
+}
+
+fn announces_seeks(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_seeked_test"));
+    controls.attach(|_| {}).unwrap();
+    controls
+        .set_playback(MediaPlayback::Paused {
+            progress: Some(MediaPosition(Duration::from_secs(3))),
+        })
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_seeked_test");
+    watch_signals(&client);
+    signals(&client);
+
+    controls
+        .notify_seeked(MediaPosition(Duration::from_millis(7_500)))
+        .unwrap();
+    let signals = signals(&client);
+    assert_eq!(signals.len(), 1);
+    let seeked = &signals[0];
+    assert_eq!(
+        seeked.interface().as_deref(),
+        Some("org.mpris.MediaPlayer2.Player")
+    );
+    assert_eq!(seeked.member().as_deref(), Some("Seeked"));
+    // The position is given in microseconds, as an `x` (int64).
+    assert_eq!(&*seeked.iter_init().signature(), "x");
+    let micros: i64 = seeked.read1().unwrap();
+    assert_eq!(micros, 7_500_000);
+
+    // The position isn't announced as a property, but is reported from now on.
+    let micros: i64 = proxy
+        .get("org.mpris.MediaPlayer2.Player", "Position")
+        .unwrap();
+    assert_eq!(micros, 7_500_000);
+
+    controls.detach().unwrap();
+}
+
+fn ignores_stale_set_position(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(bus.config("souvlaki_set_position_test"));
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_set_position_test");
+    let set_position = |track_id: &str, position: Duration| {
+        let () = proxy
+            .method_call(
+                "org.mpris.MediaPlayer2.Player",
+                "SetPosition",
+                (Path::from(track_id), position.as_micros() as i64),
+            )
+            .unwrap();
+    };
+
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("First"),
+            duration: Some(Duration::from_secs(20)),
+            ..Default::default()
+        })
+        .unwrap();
+    let first = track_id(&proxy);
+
+    // Once the track has changed, a request meant for the previous one is ignored...
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Second"),
+            duration: Some(Duration::from_secs(20)),
+            ..Default::default()
+        })
+        .unwrap();
+    let second = track_id(&proxy);
+    assert_ne!(first, second);
+
+    set_position(&first, Duration::from_secs(5));
+    assert!(received.recv_timeout(Duration::from_millis(200)).is_err());
+
+    // ...while one for the current track is handled.
+    set_position(&second, Duration::from_secs(5));
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::SetPosition(MediaPosition(Duration::from_secs(5)))
+    );
+
+    controls.detach().unwrap();
+}
+
+fn track_id(proxy: &Proxy<&Connection>) -> String {
+    let metadata: PropMap = proxy
+        .get("org.mpris.MediaPlayer2.Player", "Metadata")
+        .unwrap();
+    let track_id: &Path = arg::prop_cast(&metadata, "mpris:trackid").unwrap();
+    track_id.to_string()
+}
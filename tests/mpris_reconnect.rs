@@ -0,0 +1,58 @@
+//! Checks that the MPRIS services reconnect when the session bus restarts.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+
+use dbus::arg::{self, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use souvlaki::{MediaControlEvent, MediaMetadata};
+
+use common::{mpris_proxy, next_event, Backend, Bus};
+
+backend_tests!(reconnects_when_the_bus_restarts);
+
+fn reconnects_when_the_bus_restarts(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(bus.config("souvlaki_reconnect_test"));
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Before"),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let name = bus.name().to_string();
+    drop(bus);
+    assert_eq!(next_event(&received), MediaControlEvent::Disconnected);
+
+    // The controls can still be updated while they are disconnected.
+    controls
+        .set_metadata(MediaMetadata {
+            title: Some("Meanwhile"),
+            ..Default::default()
+        })
+        .unwrap();
+
+    let bus = Bus::start(&name).unwrap();
+    assert_eq!(next_event(&received), MediaControlEvent::Reconnected);
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_reconnect_test");
+    let metadata: PropMap = proxy
+        .get("org.mpris.MediaPlayer2.Player", "Metadata")
+        .unwrap();
+    assert_eq!(
+        arg::prop_cast::<String>(&metadata, "xesam:title").map(String::as_str),
+        Some("Meanwhile")
+    );
+
+    controls.detach().unwrap();
+}
@@ -0,0 +1,132 @@
+//! Checks the properties and methods of the `org.mpris.MediaPlayer2` interface.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::sync::mpsc;
+
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use souvlaki::{Capabilities, MediaControlEvent, PlatformConfig};
+
+use common::{mpris_proxy, next_event, Backend, Bus};
+
+backend_tests!(publishes_the_root_interface, accepts_any_uri_by_default);
+
+fn publishes_the_root_interface(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(PlatformConfig {
+        desktop_entry: "souvlaki-root-test",
+        supported_uri_schemes: &["file", "https"],
+        supported_mime_types: &["audio/mpeg", "audio/ogg"],
+        ..bus.config("souvlaki_root_test")
+    });
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_root_test");
+    let root = "org.mpris.MediaPlayer2";
+
+    let desktop_entry: String = proxy.get(root, "DesktopEntry").unwrap();
+    assert_eq!(desktop_entry, "souvlaki-root-test");
+    let schemes: Vec<String> = proxy.get(root, "SupportedUriSchemes").unwrap();
+    assert_eq!(schemes, ["file", "https"]);
+    let mime_types: Vec<String> = proxy.get(root, "SupportedMimeTypes").unwrap();
+    assert_eq!(mime_types, ["audio/mpeg", "audio/ogg"]);
+
+    // URIs are only sent if their scheme is supported.
+    let open_uri = |uri: &str| -> Result<(), dbus::Error> {
+        proxy.method_call("org.mpris.MediaPlayer2.Player", "OpenUri", (uri,))
+    };
+    let error = open_uri("ftp://example.com/song.mp3").unwrap_err();
+    assert_eq!(
+        error.name(),
+        Some("org.freedesktop.DBus.Error.NotSupported")
+    );
+    assert!(open_uri("no scheme").is_err());
+    open_uri("HTTPS://example.com/song.mp3").unwrap();
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::OpenUri("HTTPS://example.com/song.mp3".to_string())
+    );
+
+    // So are local files, if their extension stands for a supported MIME type.
+    let error = open_uri("file:///music/song.wav").unwrap_err();
+    assert_eq!(
+        error.name(),
+        Some("org.freedesktop.DBus.Error.NotSupported")
+    );
+    // The MIME type of other URIs, or of unknown extensions, is left to the handler.
+    for uri in [
+        "file:///music/Song.OGG",
+        "file:///music/song",
+        "file:///music/song.xyz",
+        "https://example.com/song.wav",
+    ] {
+        open_uri(uri).unwrap();
+        assert_eq!(
+            next_event(&received),
+            MediaControlEvent::OpenUri(uri.to_string())
+        );
+    }
+
+    // Fullscreen can't be requested until it's supported.
+    let fullscreen: bool = proxy.get(root, "Fullscreen").unwrap();
+    assert!(!fullscreen);
+    let can_set_fullscreen: bool = proxy.get(root, "CanSetFullscreen").unwrap();
+    assert!(!can_set_fullscreen);
+    proxy.set(root, "Fullscreen", true).unwrap();
+
+    controls
+        .set_capabilities(Capabilities {
+            can_set_fullscreen: true,
+            ..Default::default()
+        })
+        .unwrap();
+    proxy.set(root, "Fullscreen", true).unwrap();
+    assert_eq!(
+        next_event(&received),
+        MediaControlEvent::SetFullscreen(true)
+    );
+
+    controls.set_fullscreen(true).unwrap();
+    let fullscreen: bool = proxy.get(root, "Fullscreen").unwrap();
+    assert!(fullscreen);
+
+    controls.detach().unwrap();
+}
+
+fn accepts_any_uri_by_default(backend: Backend, bus: Bus) {
+    let (events, received) = mpsc::channel();
+    let mut controls = backend.controls(bus.config("souvlaki_any_uri_test"));
+    controls
+        .attach(move |event| events.send(event).unwrap())
+        .unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_any_uri_test");
+    let schemes: Vec<String> = proxy
+        .get("org.mpris.MediaPlayer2", "SupportedUriSchemes")
+        .unwrap();
+    assert!(schemes.is_empty());
+
+    for uri in [
+        "ftp://example.com/song.mp3",
+        "file:///song.wav",
+        "no scheme",
+    ] {
+        let () = proxy
+            .method_call("org.mpris.MediaPlayer2.Player", "OpenUri", (uri,))
+            .unwrap();
+        assert_eq!(
+            next_event(&received),
+            MediaControlEvent::OpenUri(uri.to_string())
+        );
+    }
+
+    controls.detach().unwrap();
+}
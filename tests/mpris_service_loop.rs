@@ -0,0 +1,118 @@
+//! Benchmarks the service loop of the MPRIS backends: updates must reach the bus right away,
+//! and the service thread must not wake up while nothing happens.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use std::fs;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dbus::message::MatchRule;
+use souvlaki::MediaMetadata;
+
+use common::{Backend, Bus};
+
+/// The longest an update may take to be published.
+const MAX_LATENCY: Duration = Duration::from_millis(50);
+/// How long the service is left idle while its wakeups are counted.
+const IDLE_TIME: Duration = Duration::from_secs(1);
+/// The wakeups tolerated while idle. A polling loop wakes up hundreds of times a second.
+const MAX_IDLE_WAKEUPS: u64 = 2;
+
+/// The wakeups of every thread are counted, so the backends are checked one at a time.
+static MEASURING: Mutex<()> = Mutex::new(());
+
+backend_tests!(service_loop_is_event_driven);
+
+fn service_loop_is_event_driven(backend: Backend, bus: Bus) {
+    let _measuring = MEASURING.lock().unwrap_or_else(|err| err.into_inner());
+    let client = bus.client();
+    let mut controls = backend.controls(bus.config("souvlaki_service_loop_test"));
+    controls.attach(|_| {}).unwrap();
+
+    let rule = MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+        .with_path("/org/mpris/MediaPlayer2");
+    client.add_match_no_cb(&rule.match_str()).unwrap();
+
+    // Latency: every update has to arrive well before the old one-second poll would end.
+    let mut latencies = Vec::new();
+    for i in 0..20 {
+        let title = format!("Track {}", i);
+        let sent = Instant::now();
+        controls
+            .set_metadata(MediaMetadata {
+                title: Some(&title),
+                ..Default::default()
+            })
+            .unwrap();
+        loop {
+            let message = client
+                .channel()
+                .blocking_pop_message(Duration::from_secs(5))
+                .unwrap()
+                .expect("the update was not published");
+            if message.member().as_deref() == Some("PropertiesChanged") {
+                break;
+            }
+        }
+        latencies.push(sent.elapsed());
+    }
+    // The published state is read back without a round trip.
+    let published = controls.metadata();
+    assert_eq!(published.title.as_deref(), Some("Track 19"));
+    assert!(published.track_id.is_some());
+    let max_latency = latencies.iter().max().unwrap();
+    eprintln!("max update latency: {:?}", max_latency);
+    assert!(
+        *max_latency < MAX_LATENCY,
+        "updates took up to {:?} to be published",
+        max_latency
+    );
+
+    // Wakeups: while idle, the only thread of the service must stay asleep.
+    thread::sleep(Duration::from_millis(100));
+    let before = context_switches();
+    thread::sleep(IDLE_TIME);
+    let wakeups = context_switches() - before;
+    eprintln!("wakeups in {:?} of idle time: {}", IDLE_TIME, wakeups);
+    assert!(
+        wakeups <= MAX_IDLE_WAKEUPS,
+        "the service thread woke up {} times while idle",
+        wakeups
+    );
+
+    controls.detach().unwrap();
+}
+
+/// Sums the context switches of every thread of the process except the current one.
+///
+/// The test harness runs each test on its own thread, and the main thread only waits for
+/// them, so the count comes from the service thread. The other tests wait for `MEASURING`.
+fn context_switches() -> u64 {
+    let current = fs::read_link("/proc/thread-self").unwrap();
+    let current = current.file_name().unwrap();
+
+    let mut total = 0;
+    for task in fs::read_dir("/proc/self/task").unwrap() {
+        let task = task.unwrap();
+        if task.file_name() == current {
+            continue;
+        }
+        // The thread may have exited in the meantime.
+        let status = match fs::read_to_string(task.path().join("status")) {
+            Ok(status) => status,
+            Err(_) => continue,
+        };
+        total += status
+            .lines()
+            .filter(|line| line.contains("ctxt_switches:"))
+            .filter_map(|line| line.split_whitespace().nth(1)?.parse::<u64>().ok())
+            .sum::<u64>();
+    }
+    total
+}
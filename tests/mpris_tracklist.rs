@@ -0,0 +1,232 @@
+//! Checks the `org.mpris.MediaPlayer2.TrackList` interface: the order of the tracks, its
+//! signals and its properties.
+//!
+//! Runs a private `dbus-daemon`, and is skipped if it isn't installed.
+#![cfg(target_os = "linux")]
+
+#[macro_use]
+mod common;
+
+use dbus::arg::{self, PropMap};
+use dbus::blocking::stdintf::org_freedesktop_dbus::Properties;
+use dbus::blocking::{Connection, Proxy};
+use dbus::{Message, Path};
+use souvlaki::{Error, MediaMetadata};
+
+use common::{mpris_proxy, signals, watch_signals, Backend, Bus};
+
+const TRACKLIST: &str = "org.mpris.MediaPlayer2.TrackList";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+backend_tests!(keeps_the_tracklist_in_order);
+
+fn keeps_the_tracklist_in_order(backend: Backend, bus: Bus) {
+    let mut controls = backend.controls(bus.config("souvlaki_tracklist_test"));
+    controls.attach(|_| ()).unwrap();
+
+    let client = bus.client();
+    let proxy = mpris_proxy(&client, "souvlaki_tracklist_test");
+    watch_signals(&client);
+
+    let has_tracklist: bool = proxy.get("org.mpris.MediaPlayer2", "HasTracklist").unwrap();
+    assert!(!has_tracklist);
+
+    // Inserting the first track creates the tracklist.
+    controls.insert_track("/track/b", track("B"), None).unwrap();
+    assert_eq!(
+        tracklist_signals(&client),
+        [
+            Signal::TrackAdded {
+                id: "/track/b".to_string(),
+                after: NO_TRACK.to_string(),
+            },
+            changed("org.mpris.MediaPlayer2", &["HasTracklist"]),
+            changed(TRACKLIST, &["Tracks"]),
+        ]
+    );
+    let has_tracklist: bool = proxy.get("org.mpris.MediaPlayer2", "HasTracklist").unwrap();
+    assert!(has_tracklist);
+
+    // Tracks go first without `after`, or after the given track.
+    controls.insert_track("/track/a", track("A"), None).unwrap();
+    controls
+        .insert_track("/track/c", track("C"), Some("/track/b"))
+        .unwrap();
+    controls
+        .insert_track("/track/d", track("D"), Some("/track/c"))
+        .unwrap();
+    assert_eq!(
+        tracklist_signals(&client)
+            .into_iter()
+            .filter(|signal| matches!(signal, Signal::TrackAdded { .. }))
+            .collect::<Vec<_>>(),
+        [
+            Signal::TrackAdded {
+                id: "/track/a".to_string(),
+                after: NO_TRACK.to_string(),
+            },
+            Signal::TrackAdded {
+                id: "/track/c".to_string(),
+                after: "/track/b".to_string(),
+            },
+            Signal::TrackAdded {
+                id: "/track/d".to_string(),
+                after: "/track/c".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        tracks(&proxy),
+        ["/track/a", "/track/b", "/track/c", "/track/d"]
+    );
+
+    // Updates which don't fit the tracklist are rejected, and change nothing.
+    let unknown = |result| match result {
+        Err(Error::UnknownTrackId(id)) => assert_eq!(id, "/track/missing"),
+        result => panic!("expected UnknownTrackId, got {:?}", result),
+    };
+    unknown(controls.insert_track("/track/e", track("E"), Some("/track/missing")));
+    unknown(controls.remove_track("/track/missing"));
+    unknown(controls.set_track_metadata("/track/missing", track("Missing")));
+    match controls.insert_track("/track/a", track("A"), Some("/track/d")) {
+        Err(Error::DuplicateTrackId(id)) => assert_eq!(id, "/track/a"),
+        result => panic!("expected DuplicateTrackId, got {:?}", result),
+    }
+    let result =
+        controls.set_tracklist(&[("/track/x", track("X")), ("/track/x", track("X"))], None);
+    assert!(matches!(result, Err(Error::DuplicateTrackId(_))));
+    for id in ["track", "/org/mpris/track"] {
+        assert!(matches!(
+            controls.remove_track(id),
+            Err(Error::InvalidTrackId(_))
+        ));
+        assert!(matches!(
+            controls.set_track_metadata(id, track("Invalid")),
+            Err(Error::InvalidTrackId(_))
+        ));
+    }
+    assert!(tracklist_signals(&client).is_empty());
+    assert_eq!(
+        tracks(&proxy),
+        ["/track/a", "/track/b", "/track/c", "/track/d"]
+    );
+
+    // The metadata is returned in the order of the requested ids, without the unknown ones.
+    let (metadata,): (Vec<PropMap>,) = proxy
+        .method_call(
+            TRACKLIST,
+            "GetTracksMetadata",
+            (vec![
+                Path::from("/track/c"),
+                Path::from("/track/missing"),
+                Path::from("/track/a"),
+            ],),
+        )
+        .unwrap();
+    let titles: Vec<&str> = metadata
+        .iter()
+        .map(|metadata| {
+            arg::prop_cast::<String>(metadata, "xesam:title")
+                .unwrap()
+                .as_str()
+        })
+        .collect();
+    assert_eq!(titles, ["C", "A"]);
+
+    controls.remove_track("/track/b").unwrap();
+    assert_eq!(
+        tracklist_signals(&client),
+        [
+            Signal::TrackRemoved("/track/b".to_string()),
+            changed(TRACKLIST, &["Tracks"]),
+        ]
+    );
+    assert_eq!(tracks(&proxy), ["/track/a", "/track/c", "/track/d"]);
+
+    // Replacing the tracklist doesn't change `HasTracklist` again.
+    controls
+        .set_tracklist(
+            &[("/track/x", track("X")), ("/track/y", track("Y"))],
+            Some("/track/y"),
+        )
+        .unwrap();
+    assert_eq!(
+        tracklist_signals(&client),
+        [
+            Signal::TrackListReplaced(
+                vec!["/track/x".to_string(), "/track/y".to_string()],
+                "/track/y".to_string(),
+            ),
+            changed(TRACKLIST, &["Tracks"]),
+        ]
+    );
+    assert_eq!(tracks(&proxy), ["/track/x", "/track/y"]);
+
+    controls.detach().unwrap();
+}
+
+fn track(title: &str) -> MediaMetadata<'_> {
+    MediaMetadata {
+        title: Some(title),
+        ..Default::default()
+    }
+}
+
+fn tracks(proxy: &Proxy<&Connection>) -> Vec<String> {
+    let tracks: Vec<Path> = proxy.get(TRACKLIST, "Tracks").unwrap();
+    tracks.iter().map(|track| track.to_string()).collect()
+}
+
+/// A signal of the MPRIS object, with the arguments checked by this test.
+#[derive(PartialEq, Debug)]
+enum Signal {
+    TrackListReplaced(Vec<String>, String),
+    TrackAdded {
+        id: String,
+        after: String,
+    },
+    TrackRemoved(String),
+    /// The interface, and the sorted names of the changed and invalidated properties.
+    PropertiesChanged(String, Vec<String>),
+}
+
+fn changed(interface: &str, names: &[&str]) -> Signal {
+    let names = names.iter().map(|name| name.to_string()).collect();
+    Signal::PropertiesChanged(interface.to_string(), names)
+}
+
+fn tracklist_signals(client: &Connection) -> Vec<Signal> {
+    signals(client).iter().map(parse_signal).collect()
+}
+
+fn parse_signal(message: &Message) -> Signal {
+    match message.member().as_deref().unwrap() {
+        "TrackListReplaced" => {
+            let (tracks, current): (Vec<Path>, Path) = message.read2().unwrap();
+            Signal::TrackListReplaced(
+                tracks.iter().map(|track| track.to_string()).collect(),
+                current.to_string(),
+            )
+        }
+        "TrackAdded" => {
+            let (metadata, after): (PropMap, Path) = message.read2().unwrap();
+            let id: &Path = arg::prop_cast(&metadata, "mpris:trackid").unwrap();
+            Signal::TrackAdded {
+                id: id.to_string(),
+                after: after.to_string(),
+            }
+        }
+        "TrackRemoved" => {
+            let id: Path = message.read1().unwrap();
+            Signal::TrackRemoved(id.to_string())
+        }
+        "PropertiesChanged" => {
+            let (interface, changed, invalidated): (String, PropMap, Vec<String>) =
+                message.read3().unwrap();
+            let mut names: Vec<String> = changed.into_keys().chain(invalidated).collect();
+            names.sort();
+            Signal::PropertiesChanged(interface, names)
+        }
+        member => panic!("unexpected signal {}", member),
+    }
+}
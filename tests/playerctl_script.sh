@@ -15,9 +15,11 @@ playerctl position 10-
 playerctl position 10+
 playerctl volume 0.5
 playerctl open "https://testlink.com"
-# TODO: Shuffle and repeat.
-# playerctl shuffle
-# playerctl repeat
+playerctl shuffle on
+playerctl shuffle off
+playerctl loop Track
+playerctl loop Playlist
+playerctl loop None
 
 # The following are commands not supported by playerctl, thus we use dbus-send
 call() {